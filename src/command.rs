@@ -0,0 +1,887 @@
+//! Commands typed in after `:`, which the keys run as well
+
+use crate::{
+    config::{Config, Content, Field},
+    mpd::{AddMode, Entry, ModeState, Mpd},
+    ui::{
+        app::{App, BROWSE_TAB, LIBRARY_TAB, PLAYLISTS_TAB, QUEUE_TAB, SEARCH_TAB},
+        history::History,
+        queue::SortKey,
+        search::SearchScope,
+    },
+};
+use serde::{
+    de::{self, value::StrDeserializer},
+    Deserialize,
+};
+use std::{str::FromStr, time::Duration};
+
+/// Names the tabs go by in `tab`, in order
+const TABS: [&str; 5] = ["queue", "browse", "library", "playlists", "search"];
+
+/// Fields `sort` completes to, any other field of the columns works as well
+const SORT_FIELDS: [&str; 13] = [
+    "artist",
+    "albumartist",
+    "album",
+    "date",
+    "disc",
+    "track",
+    "title",
+    "genre",
+    "composer",
+    "filename",
+    "file",
+    "duration",
+    "priority",
+];
+
+#[derive(Debug, Clone, Copy)]
+/// What the argument of a command is completed to
+enum Completion {
+    Nothing,
+    /// Path of a song or directory in the database
+    Path,
+    Playlist,
+    Words(&'static [&'static str]),
+    /// `scope=` prefixes of the search
+    Scope,
+    Setting,
+    /// Every word is a field, see `SORT_FIELDS`
+    Fields,
+}
+
+/// Name of a command, the arguments it takes and how they are completed
+struct CommandInfo {
+    name: &'static str,
+    usage: &'static str,
+    completion: Completion,
+}
+
+const COMMANDS: [CommandInfo; 47] = [
+    CommandInfo {
+        name: "quit",
+        usage: "quit",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "tab",
+        usage: "tab [number|name]",
+        completion: Completion::Words(&TABS),
+    },
+    CommandInfo {
+        name: "pause",
+        usage: "pause",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "vol",
+        usage: "vol N|+N|-N",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "down",
+        usage: "down",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "up",
+        usage: "up",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "open",
+        usage: "open",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "back",
+        usage: "back",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "delete",
+        usage: "delete",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "move",
+        usage: "move +N|-N",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "crop",
+        usage: "crop",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "clear",
+        usage: "clear",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "shuffle",
+        usage: "shuffle",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "mark",
+        usage: "mark",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "visual",
+        usage: "visual",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "unmark",
+        usage: "unmark",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "sort",
+        usage: "sort [[-]field...]",
+        completion: Completion::Fields,
+    },
+    CommandInfo {
+        name: "group",
+        usage: "group [on|off|toggle]",
+        completion: Completion::Words(&["on", "off", "toggle"]),
+    },
+    CommandInfo {
+        name: "undo",
+        usage: "undo",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "redo",
+        usage: "redo",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "addto",
+        usage: "addto [playlist]",
+        completion: Completion::Playlist,
+    },
+    CommandInfo {
+        name: "priority",
+        usage: "priority N|+N|-N",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "add",
+        usage: "add [path]",
+        completion: Completion::Path,
+    },
+    CommandInfo {
+        name: "insert",
+        usage: "insert [path]",
+        completion: Completion::Path,
+    },
+    CommandInfo {
+        name: "replace",
+        usage: "replace [path]",
+        completion: Completion::Path,
+    },
+    CommandInfo {
+        name: "rename",
+        usage: "rename [name]",
+        completion: Completion::Playlist,
+    },
+    CommandInfo {
+        name: "save",
+        usage: "save [name]",
+        completion: Completion::Playlist,
+    },
+    CommandInfo {
+        name: "search",
+        usage: "search [[tag=]query]",
+        completion: Completion::Scope,
+    },
+    CommandInfo {
+        name: "filter",
+        usage: "filter [pattern]",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "scope",
+        usage: "scope",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "exact",
+        usage: "exact",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "addall",
+        usage: "addall",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "match",
+        usage: "match next|prev",
+        completion: Completion::Words(&["next", "prev"]),
+    },
+    CommandInfo {
+        name: "only-matches",
+        usage: "only-matches",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "find",
+        usage: "find",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "next",
+        usage: "next",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "prev",
+        usage: "prev",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "stop",
+        usage: "stop",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "seek",
+        usage: "seek forward|backward|+N|-N|[m:]ss|N%",
+        completion: Completion::Words(&["forward", "backward"]),
+    },
+    CommandInfo {
+        name: "repeat",
+        usage: "repeat [on|off|toggle]",
+        completion: Completion::Words(&["on", "off", "toggle"]),
+    },
+    CommandInfo {
+        name: "random",
+        usage: "random [on|off|toggle]",
+        completion: Completion::Words(&["on", "off", "toggle"]),
+    },
+    CommandInfo {
+        name: "single",
+        usage: "single [on|off|oneshot]",
+        completion: Completion::Words(&["on", "off", "oneshot"]),
+    },
+    CommandInfo {
+        name: "consume",
+        usage: "consume [on|off|oneshot]",
+        completion: Completion::Words(&["on", "off", "oneshot"]),
+    },
+    CommandInfo {
+        name: "crossfade",
+        usage: "crossfade [seconds]",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "set",
+        usage: "set name value",
+        completion: Completion::Setting,
+    },
+    CommandInfo {
+        name: "help",
+        usage: "help",
+        completion: Completion::Nothing,
+    },
+    CommandInfo {
+        name: "command-line",
+        usage: "command-line",
+        completion: Completion::Nothing,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// New value of a number, either as it is or relative to the current one
+pub enum Change {
+    To(u32),
+    By(i64),
+}
+
+impl Change {
+    fn apply(self, value: i64) -> i64 {
+        match self {
+            Self::To(to) => to.into(),
+            Self::By(delta) => value + delta,
+        }
+    }
+}
+
+impl FromStr for Change {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(delta) = s.strip_prefix('+') {
+            Ok(Self::By(number(delta)?))
+        } else if let Some(delta) = s.strip_prefix('-') {
+            Ok(Self::By(-number::<i64>(delta)?))
+        } else {
+            Ok(Self::To(number(s)?))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Seek {
+    /// By the seek step of the config
+    Forward,
+    Backward,
+    /// By seconds from the elapsed time
+    By(i64),
+    To(Duration),
+    /// To the fraction of the song, from 0 to 1
+    Ratio(f64),
+}
+
+impl FromStr for Seek {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forward" => return Ok(Self::Forward),
+            "backward" => return Ok(Self::Backward),
+            _ => (),
+        }
+        if let Some(percent) = s.strip_suffix('%') {
+            return Ok(Self::Ratio(number::<f64>(percent)? / 100.0));
+        }
+        match s.parse() {
+            Ok(Change::By(delta)) => Ok(Self::By(delta)),
+            // seconds, with minutes and hours in front of them
+            _ => {
+                let seconds = s.split(':').try_fold(0, |total, part| {
+                    Ok::<_, String>(total * 60 + number::<u64>(part)?)
+                })?;
+                Ok(Self::To(Duration::from_secs(seconds)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Everything the keys and the command line can do
+pub enum Command {
+    Quit,
+    /// Goes to the tab, or to the next one if there is none
+    Tab(Option<usize>),
+    Pause,
+    Volume(Change),
+    Down,
+    Up,
+    Open,
+    Back,
+    Delete,
+    Move(isize),
+    Crop,
+    Clear,
+    Shuffle,
+    Mark,
+    Visual,
+    Unmark,
+    /// Sorts the queue by the keys, the first one first
+    Sort(Vec<SortKey>),
+    /// Shows the album headers in the queue, toggling them if `None`
+    Group(Option<bool>),
+    Undo,
+    Redo,
+    /// Adds the marked songs to the playlist, or asks for one
+    AddTo(Option<String>),
+    Priority(Change),
+    /// Adds the song or directory at the path, or the one selected on the tab
+    Add(AddMode, Option<String>),
+    /// Renames the selected playlist, or asks for a name
+    Rename(Option<String>),
+    /// Saves the queue as a playlist, or asks for a name
+    Save(Option<String>),
+    /// Searches for the query, or starts typing one
+    Search(Option<(SearchScope, String)>),
+    /// Searches the queue for the pattern, or starts typing one
+    Filter(Option<String>),
+    Scope,
+    Exact,
+    AddAll,
+    /// Jumps to the next match if true, to the previous one otherwise
+    Match(bool),
+    OnlyMatches,
+    Find,
+    Next,
+    Prev,
+    Stop,
+    Seek(Seek),
+    /// Turns the mode on or off, toggling it if `None`
+    Repeat(Option<bool>),
+    Random(Option<bool>),
+    /// Sets the mode, switching to the next one if `None`
+    Single(Option<ModeState>),
+    Consume(Option<ModeState>),
+    /// Sets the crossfade, or asks for it
+    Crossfade(Option<u32>),
+    Set(String, String),
+    Help,
+    OpenCommandLine,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, arg) = s
+            .split_once(char::is_whitespace)
+            .map_or((s, ""), |(name, arg)| (name, arg.trim()));
+        let info = COMMANDS
+            .iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| format!("unknown command `{}`", name))?;
+        let usage = || format!("usage: {}", info.usage);
+        let optional = || (!arg.is_empty()).then(|| arg.to_string());
+        let no_arg = |command: Self| {
+            if arg.is_empty() {
+                Ok(command)
+            } else {
+                Err(format!("`{}` takes no argument", name))
+            }
+        };
+        let add = |mode| Ok(Self::Add(mode, optional()));
+
+        match name {
+            "quit" => no_arg(Self::Quit),
+            "tab" if arg.is_empty() => Ok(Self::Tab(None)),
+            "tab" => {
+                let by_name = TABS.iter().position(|tab| tab.eq_ignore_ascii_case(arg));
+                let by_number = arg
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=TABS.len()).contains(n))
+                    .map(|n| n - 1);
+                by_name
+                    .or(by_number)
+                    .map(|tab| Self::Tab(Some(tab)))
+                    .ok_or_else(usage)
+            }
+            "pause" => no_arg(Self::Pause),
+            "vol" => arg.parse().map(Self::Volume).map_err(|_| usage()),
+            "down" => no_arg(Self::Down),
+            "up" => no_arg(Self::Up),
+            "open" => no_arg(Self::Open),
+            "back" => no_arg(Self::Back),
+            "delete" => no_arg(Self::Delete),
+            "move" => match arg.parse() {
+                Ok(Change::By(delta)) => Ok(Self::Move(delta as isize)),
+                _ => Err(usage()),
+            },
+            "crop" => no_arg(Self::Crop),
+            "clear" => no_arg(Self::Clear),
+            "shuffle" => no_arg(Self::Shuffle),
+            "mark" => no_arg(Self::Mark),
+            "visual" => no_arg(Self::Visual),
+            "unmark" => no_arg(Self::Unmark),
+            "sort" => sort_keys(arg).map(Self::Sort),
+            "group" => switch(arg).map(Self::Group).ok_or_else(usage),
+            "undo" => no_arg(Self::Undo),
+            "redo" => no_arg(Self::Redo),
+            "addto" => Ok(Self::AddTo(optional())),
+            "priority" => arg.parse().map(Self::Priority).map_err(|_| usage()),
+            "add" => add(AddMode::Append),
+            "insert" => add(AddMode::InsertNext),
+            "replace" => add(AddMode::Replace),
+            "rename" => Ok(Self::Rename(optional())),
+            "save" => Ok(Self::Save(optional())),
+            "search" if arg.is_empty() => Ok(Self::Search(None)),
+            "search" => {
+                let scoped = arg.split_once('=').and_then(|(scope, query)| {
+                    Some((SearchScope::from_name(scope.trim())?, query.trim()))
+                });
+                let (scope, query) = scoped.unwrap_or((SearchScope::Any, arg));
+                Ok(Self::Search(Some((scope, query.to_string()))))
+            }
+            "filter" => Ok(Self::Filter(optional())),
+            "scope" => no_arg(Self::Scope),
+            "exact" => no_arg(Self::Exact),
+            "addall" => no_arg(Self::AddAll),
+            "match" => match arg {
+                "next" => Ok(Self::Match(true)),
+                "prev" => Ok(Self::Match(false)),
+                _ => Err(usage()),
+            },
+            "only-matches" => no_arg(Self::OnlyMatches),
+            "find" => no_arg(Self::Find),
+            "next" => no_arg(Self::Next),
+            "prev" => no_arg(Self::Prev),
+            "stop" => no_arg(Self::Stop),
+            "seek" => arg.parse().map(Self::Seek).map_err(|_| usage()),
+            "repeat" => switch(arg).map(Self::Repeat).ok_or_else(usage),
+            "random" => switch(arg).map(Self::Random).ok_or_else(usage),
+            "single" => mode(arg).map(Self::Single).ok_or_else(usage),
+            "consume" => mode(arg).map(Self::Consume).ok_or_else(usage),
+            "crossfade" if arg.is_empty() => Ok(Self::Crossfade(None)),
+            "crossfade" => arg
+                .parse()
+                .map(|seconds| Self::Crossfade(Some(seconds)))
+                .map_err(|_| usage()),
+            "set" => {
+                let (setting, value) = arg.split_once(char::is_whitespace).ok_or_else(usage)?;
+                Ok(Self::Set(setting.to_string(), value.trim().to_string()))
+            }
+            "help" => no_arg(Self::Help),
+            "command-line" => no_arg(Self::OpenCommandLine),
+            _ => Err(format!("unknown command `{}`", name)),
+        }
+    }
+}
+
+/// Fields like `artist -date`, a `-` sorting by the field descending.
+/// Nothing sorts by the album artist, date, album, disc and track
+fn sort_keys(arg: &str) -> Result<Vec<SortKey>, String> {
+    if arg.is_empty() {
+        let fields = [
+            vec![Field::Tag("AlbumArtist"), Field::Tag("Artist")],
+            vec![Field::Tag("Date")],
+            vec![Field::Tag("Album")],
+            vec![Field::Tag("Disc")],
+            vec![Field::Tag("Track")],
+        ];
+        return Ok(fields
+            .into_iter()
+            .map(|fields| SortKey::new(Content::Fields(fields), false))
+            .collect());
+    }
+
+    arg.split_whitespace()
+        .map(|word| {
+            let (name, descending) = word
+                .strip_prefix('-')
+                .map_or((word, false), |name| (name, true));
+            let field = Field::deserialize(StrDeserializer::<de::value::Error>::new(name))
+                .map_err(|e| e.to_string())?;
+            Ok(SortKey::new(Content::Fields(vec![field]), descending))
+        })
+        .collect()
+}
+
+impl Command {
+    /// Whether the command can change the queue on the tab, the changes are kept to be undone.
+    /// Opening only adds songs on the search tab, elsewhere it moves around
+    const fn edits_queue(&self, tab: usize) -> bool {
+        match self {
+            Self::Delete
+            | Self::Move(_)
+            | Self::Crop
+            | Self::Clear
+            | Self::Shuffle
+            | Self::Sort(_)
+            | Self::Add(..)
+            | Self::AddAll => true,
+            Self::Open => tab == SEARCH_TAB,
+            _ => false,
+        }
+    }
+}
+
+fn number<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("`{}` is not a number", s))
+}
+
+/// `on`, `off`, or `toggle` which is the same as nothing
+fn switch(arg: &str) -> Option<Option<bool>> {
+    match arg {
+        "" | "toggle" => Some(None),
+        "on" => Some(Some(true)),
+        "off" => Some(Some(false)),
+        _ => None,
+    }
+}
+
+/// `off`, `on` or `oneshot`, nothing switching to the next one
+fn mode(arg: &str) -> Option<Option<ModeState>> {
+    match arg {
+        "" => Some(None),
+        "off" => Some(Some(ModeState::Off)),
+        "on" => Some(Some(ModeState::On)),
+        "oneshot" => Some(Some(ModeState::Oneshot)),
+        _ => None,
+    }
+}
+
+/// Runs the command on the current tab. `Quit` is left to the caller
+pub(crate) fn run(
+    app: &mut App,
+    mpd: &mut Mpd,
+    config: &mut Config,
+    command: Command,
+) -> Result<(), String> {
+    let before = command
+        .edits_queue(app.tab_index())
+        .then(|| History::snapshot(mpd))
+        .flatten();
+    let selected_id = app.selected_id(mpd);
+    execute(app, mpd, config, command)?;
+    if let Some(before) = before {
+        app.record_queue(mpd, before, selected_id);
+    }
+    Ok(())
+}
+
+fn execute(
+    app: &mut App,
+    mpd: &mut Mpd,
+    config: &mut Config,
+    command: Command,
+) -> Result<(), String> {
+    let tab = app.tab_index();
+    let not_here = |name: &str| Err(format!("`{}` does nothing on this tab", name));
+    match command {
+        Command::Quit => (),
+        Command::Tab(None) => app.tab_next(mpd),
+        Command::Tab(Some(tab)) => app.select_tab(mpd, tab),
+        Command::Pause => {
+            mpd.command(|client| client.toggle_pause());
+        }
+        Command::Volume(change) => {
+            let volume = change.apply(mpd.status().volume.into()).clamp(0, 100) as i8;
+            mpd.command(|client| client.volume(volume));
+        }
+        Command::Down => match tab {
+            BROWSE_TAB => app.browser_mut().next(),
+            LIBRARY_TAB => app.library_mut().next(mpd),
+            PLAYLISTS_TAB => app.playlists_mut().next(mpd),
+            SEARCH_TAB => app.search_mut().next(),
+            _ => app.next(),
+        },
+        Command::Up => match tab {
+            BROWSE_TAB => app.browser_mut().previous(),
+            LIBRARY_TAB => app.library_mut().previous(mpd),
+            PLAYLISTS_TAB => app.playlists_mut().previous(mpd),
+            SEARCH_TAB => app.search_mut().previous(),
+            _ => app.previous(),
+        },
+        Command::Open => match tab {
+            BROWSE_TAB => app.browser_mut().open(mpd),
+            LIBRARY_TAB => app.library_mut().column_right(),
+            PLAYLISTS_TAB => app.playlists_mut().open(),
+            SEARCH_TAB => app.search_mut().add(mpd, AddMode::Play),
+            _ => app.switch(mpd),
+        },
+        Command::Back => match tab {
+            BROWSE_TAB => app.browser_mut().back(mpd),
+            LIBRARY_TAB => app.library_mut().column_left(),
+            PLAYLISTS_TAB => app.playlists_mut().back(),
+            _ => return not_here("back"),
+        },
+        Command::Delete => match tab {
+            QUEUE_TAB => app.delete(mpd),
+            PLAYLISTS_TAB => {
+                let prompt = app.playlists_mut().delete_prompt(mpd);
+                app.open_prompt(prompt);
+            }
+            _ => return not_here("delete"),
+        },
+        Command::Move(delta) => match tab {
+            QUEUE_TAB => app.move_selected(mpd, delta),
+            PLAYLISTS_TAB => app.playlists_mut().move_song(mpd, delta),
+            _ => return not_here("move"),
+        },
+        Command::Crop => app.crop(mpd),
+        Command::Clear => app.clear(mpd),
+        Command::Shuffle => app.shuffle(mpd),
+        Command::Mark => app.toggle_mark(mpd),
+        Command::Visual => app.toggle_visual(mpd),
+        Command::Unmark => app.clear_marks(),
+        Command::Sort(keys) => app.sort_queue(mpd, &keys),
+        Command::Group(Some(grouped)) => app.set_grouped(grouped),
+        Command::Group(None) => app.set_grouped(!app.grouped()),
+        Command::Undo => app.undo(mpd)?,
+        Command::Redo => app.redo(mpd)?,
+        Command::AddTo(None) => app.open_playlist_chooser(mpd),
+        Command::AddTo(Some(playlist)) => app.add_to_playlist(mpd, &playlist),
+        Command::Priority(change) => app.change_priority(mpd, |prio| {
+            change.apply(prio.into()).clamp(0, u8::MAX.into()) as u8
+        }),
+        Command::Add(mode, Some(path)) => add_path(mpd, &path, mode)?,
+        Command::Add(mode, None) => match tab {
+            BROWSE_TAB => app.browser_mut().add(mpd, mode),
+            LIBRARY_TAB => app.library_mut().add(mpd, mode),
+            PLAYLISTS_TAB => app.playlists_mut().add(mpd, mode),
+            SEARCH_TAB => app.search_mut().add(mpd, mode),
+            QUEUE_TAB if mode == AddMode::InsertNext => app.play_next(mpd),
+            QUEUE_TAB if mode == AddMode::Append => app.play_last(mpd),
+            _ => return not_here("add"),
+        },
+        Command::Rename(name) => {
+            if tab != PLAYLISTS_TAB {
+                return not_here("rename");
+            }
+            match name {
+                Some(to) => {
+                    let Some(name) = app.playlists_mut().selected_name(mpd) else {
+                        return Ok(());
+                    };
+                    app.rename_playlist(mpd, &name, &to);
+                }
+                None => {
+                    let prompt = app.playlists_mut().rename_prompt(mpd);
+                    app.open_prompt(prompt);
+                }
+            }
+        }
+        Command::Save(Some(name)) => app.save_queue(mpd, &name),
+        Command::Save(None) => {
+            let prompt = app.save_queue_prompt(mpd);
+            app.open_prompt(Some(prompt));
+        }
+        Command::Search(query) => {
+            app.select_tab(mpd, SEARCH_TAB);
+            match query {
+                Some((scope, query)) => app.set_search(mpd, scope, &query),
+                None => app.search_mut().start_editing(),
+            }
+        }
+        Command::Filter(pattern) => {
+            app.select_tab(mpd, QUEUE_TAB);
+            match pattern {
+                Some(pattern) => app.set_filter(mpd, &pattern),
+                None => app.start_filter(),
+            }
+        }
+        Command::Scope => app.search_mut().next_scope(mpd),
+        Command::Exact => app.search_mut().toggle_exact(mpd),
+        Command::AddAll => app.search_mut().add_all(mpd),
+        Command::Match(forward) => app.jump_match(forward),
+        Command::OnlyMatches => app.toggle_filter(),
+        Command::Find => app.finder_mut().open(),
+        Command::Next => {
+            mpd.command(|client| client.next());
+        }
+        Command::Prev => {
+            mpd.command(|client| client.prev());
+        }
+        Command::Stop => mpd.stop(),
+        Command::Seek(seek) => {
+            let step = i64::from(config.playback().seek_step());
+            match seek {
+                Seek::Forward => mpd.seek_by(step),
+                Seek::Backward => mpd.seek_by(-step),
+                Seek::By(delta) => mpd.seek_by(delta),
+                Seek::To(position) => mpd.seek_to(position),
+                Seek::Ratio(ratio) => mpd.seek_ratio(ratio),
+            }
+        }
+        Command::Repeat(Some(repeat)) => mpd.set_repeat(repeat),
+        Command::Repeat(None) => mpd.toggle_repeat(),
+        Command::Random(Some(random)) => mpd.set_random(random),
+        Command::Random(None) => mpd.toggle_random(),
+        Command::Single(Some(single)) => mpd.set_single(single),
+        Command::Single(None) => mpd.cycle_single(),
+        Command::Consume(Some(consume)) => mpd.set_consume(consume),
+        Command::Consume(None) => mpd.cycle_consume(),
+        Command::Crossfade(Some(seconds)) => mpd.set_crossfade(seconds),
+        Command::Crossfade(None) => app.open_crossfade_prompt(mpd),
+        Command::Set(name, value) => {
+            config.set(&name, &value)?;
+            if name.starts_with("library.") {
+                app.reset_library(mpd, config);
+            }
+            if name == "undo.depth" {
+                app.set_undo_depth(config.undo().depth());
+            }
+        }
+        Command::Help => app.show_popup = !app.show_popup,
+        Command::OpenCommandLine => app.command_line_mut().open(),
+    }
+    Ok(())
+}
+
+/// Adds the song at the path, or every song inside the directory
+fn add_path(mpd: &mut Mpd, path: &str, mode: AddMode) -> Result<(), String> {
+    let path = path.trim_end_matches('/');
+    let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+    let entry = mpd
+        .list_directory(parent)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|entry| match entry {
+            Entry::Directory(dir) => dir == path,
+            Entry::Song(song) => song.file == path,
+        });
+    let songs = match entry {
+        Some(Entry::Song(song)) => vec![song],
+        Some(Entry::Directory(dir)) => mpd.songs_in_directory(&dir).unwrap_or_default(),
+        None if path.is_empty() => mpd.songs_in_directory("").unwrap_or_default(),
+        None => return Err(format!("`{}` is not in the database", path)),
+    };
+    mpd.add_songs(&songs, mode);
+    Ok(())
+}
+
+/// Where the word being typed at the end of the line starts and what it can be completed to
+pub(crate) fn complete(line: &str, mpd: &mut Mpd) -> (usize, Vec<String>) {
+    let start = line.len() - line.trim_start().len();
+    let Some((name, arg)) = line[start..].split_once(char::is_whitespace) else {
+        let word = &line[start..];
+        let names = COMMANDS
+            .iter()
+            .filter(|info| info.name.starts_with(word))
+            .map(|info| format!("{} ", info.name))
+            .collect();
+        return (start, names);
+    };
+    let Some(info) = COMMANDS.iter().find(|info| info.name == name) else {
+        return (line.len(), Vec::new());
+    };
+    let arg = arg.trim_start();
+    let start = line.len() - arg.len();
+
+    let candidates: Vec<String> = match info.completion {
+        Completion::Nothing => Vec::new(),
+        Completion::Path => {
+            let dir = arg.rsplit_once('/').map_or("", |(dir, _)| dir);
+            mpd.list_directory(dir)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| match entry {
+                    Entry::Directory(dir) => format!("{}/", dir),
+                    Entry::Song(song) => song.file,
+                })
+                .collect()
+        }
+        Completion::Playlist => mpd
+            .playlists()
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .map(|playlist| playlist.name.clone())
+            .collect(),
+        Completion::Words(words) => words.iter().map(ToString::to_string).collect(),
+        Completion::Scope => SearchScope::all()
+            .map(|scope| format!("{}=", scope.name().to_lowercase()))
+            .collect(),
+        // only the name of the setting, values are free-form
+        Completion::Setting if arg.contains(char::is_whitespace) => Vec::new(),
+        Completion::Setting => Config::settings()
+            .map(|setting| format!("{} ", setting))
+            .collect(),
+        Completion::Fields => {
+            // only the last word, keeping its `-`
+            let word_start = arg.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+            let word = &arg[word_start..];
+            let (sign, name) = word
+                .strip_prefix('-')
+                .map_or(("", word), |name| ("-", name));
+            let fields = SORT_FIELDS
+                .iter()
+                .filter(|field| field.starts_with(name))
+                .map(|field| format!("{}{} ", sign, field))
+                .collect();
+            return (start + word_start, fields);
+        }
+    };
+    let candidates = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(arg))
+        .collect();
+    (start, candidates)
+}
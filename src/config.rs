@@ -1,51 +1,299 @@
 //! Parses config
 
-use crossterm::event::KeyCode;
-use std::error::Error;
+use crate::{
+    template::Template,
+    ui::app::{BROWSE_TAB, LIBRARY_TAB, PLAYLISTS_TAB, QUEUE_TAB, SEARCH_TAB},
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{
+    de::{self, value::StrDeserializer},
+    Deserialize, Deserializer,
+};
+use std::{collections::HashMap, env, error::Error, fs, io, path::PathBuf};
 use tui::style::{Color, Modifier, Style};
 
-#[allow(dead_code)]
-#[derive(Debug)]
-pub enum PlaylistLayout {
+/// Tags MPD knows, as it names them
+const TAGS: [&str; 34] = [
+    "Artist",
+    "ArtistSort",
+    "Album",
+    "AlbumSort",
+    "AlbumArtist",
+    "AlbumArtistSort",
+    "Title",
+    "TitleSort",
+    "Track",
+    "Name",
+    "Genre",
+    "Mood",
+    "Date",
+    "OriginalDate",
+    "Composer",
+    "ComposerSort",
+    "Performer",
+    "Conductor",
+    "Work",
+    "Ensemble",
+    "Movement",
+    "MovementNumber",
+    "Location",
+    "Grouping",
+    "Comment",
+    "Disc",
+    "Label",
+    "MUSICBRAINZ_ARTISTID",
+    "MUSICBRAINZ_ALBUMID",
+    "MUSICBRAINZ_ALBUMARTISTID",
+    "MUSICBRAINZ_TRACKID",
+    "MUSICBRAINZ_RELEASETRACKID",
+    "MUSICBRAINZ_RELEASEGROUPID",
+    "MUSICBRAINZ_WORKID",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What a column of the queue shows
+pub enum Field {
     File,
-    Title,
+    /// Last part of the path
+    Filename,
+    Extension,
     Duration,
-    Album,
-    Artist,
-    Track,
+    /// Position in the queue, from 1
+    Position,
+    Priority,
+    /// Only known for the playing song
+    Bitrate,
+    /// Sample rate, bits and channels
+    Format,
+    /// Any tag, as MPD names it
+    Tag(&'static str),
+}
+
+impl Field {
+    pub(crate) const fn title(self) -> &'static str {
+        match self {
+            Self::File => "File",
+            Self::Filename => "Filename",
+            Self::Extension => "Ext",
+            Self::Duration => "Duration",
+            Self::Position => "#",
+            Self::Priority => "Prio",
+            Self::Bitrate => "Bitrate",
+            Self::Format => "Format",
+            Self::Tag(tag) => tag,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let field = match name.as_str() {
+            "file" => Self::File,
+            "filename" => Self::Filename,
+            "extension" => Self::Extension,
+            "duration" => Self::Duration,
+            "position" => Self::Position,
+            "priority" => Self::Priority,
+            "bitrate" => Self::Bitrate,
+            "format" => Self::Format,
+            // tags are matched ignoring the case and underscores, `album_artist` is `AlbumArtist`
+            _ => {
+                let normalize = |name: &str| name.replace('_', "").to_lowercase();
+                let tag = TAGS
+                    .iter()
+                    .find(|tag| normalize(tag) == normalize(&name))
+                    .ok_or_else(|| de::Error::custom(format!("unknown field `{}`", name)))?;
+                Self::Tag(tag)
+            }
+        };
+        Ok(field)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    /// Of the width left by the other columns
+    Percent(u16),
+    /// In cells
+    Fixed(u16),
+    /// As wide as the widest value
+    Fit,
+}
+
+impl<'de> Deserialize<'de> for Width {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum WidthDef {
+            Percent(u16),
+            Text(String),
+        }
+
+        let text = match WidthDef::deserialize(deserializer)? {
+            WidthDef::Percent(percent) => return Ok(Self::Percent(percent)),
+            WidthDef::Text(text) => text,
+        };
+        let number = |n: &str| n.trim().parse().ok();
+        let width = if text == "fit" {
+            Some(Self::Fit)
+        } else if let Some(percent) = text.strip_suffix('%') {
+            number(percent).map(Self::Percent)
+        } else {
+            text.strip_suffix("ch").and_then(number).map(Self::Fixed)
+        };
+        width.ok_or_else(|| {
+            de::Error::custom(format!(
+                "invalid width `{}`, expected a percent like `20%`, cells like `12ch` or `fit`",
+                text
+            ))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+/// What a column of the queue shows
+pub enum Content {
+    /// Tried in order until one is not empty
+    Fields(Vec<Field>),
+    Format(Template),
+}
+
+#[derive(Debug, Clone)]
+/// Column of the queue
+pub struct Column {
+    content: Content,
+    title: String,
+    width: Width,
+    align: Align,
+}
+
+impl Column {
+    fn new(fields: Vec<Field>, width: Width, align: Align) -> Self {
+        Self {
+            title: fields[0].title().to_string(),
+            content: Content::Fields(fields),
+            width,
+            align,
+        }
+    }
+
+    pub(crate) const fn content(&self) -> &Content {
+        &self.content
+    }
+
+    pub(crate) fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub(crate) const fn width(&self) -> Width {
+        self.width
+    }
+
+    pub(crate) const fn align(&self) -> Align {
+        self.align
+    }
+}
+
+pub const USAGE: &str = "\
+Usage: empirust [OPTIONS]
+
+Options:
+  --host <[PASSWORD@]HOST>  MPD host, a path is treated as a Unix socket [env: MPD_HOST]
+  --port <PORT>             MPD port [env: MPD_PORT]
+  --socket <PATH>           Path to MPD's Unix socket
+  -h, --help                Print help";
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+    connection: Connection,
     styles: Styles,
     keys: Keys,
-    // is in form of: Type of column, its width in percents
-    playlist_layout: Vec<(PlaylistLayout, u16)>,
+    library: Library,
+    playback: Playback,
+    undo: Undo,
+    formats: Formats,
+    #[serde(deserialize_with = "deserialize_playlist_layout")]
+    playlist_layout: Vec<Column>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            connection: Connection::default(),
             styles: Styles::default(),
             keys: Keys::default(),
+            library: Library::default(),
+            playback: Playback::default(),
+            undo: Undo::default(),
+            formats: Formats::default(),
             playlist_layout: vec![
-                (PlaylistLayout::Artist, 20),
-                (PlaylistLayout::Track, 5),
-                (PlaylistLayout::Title, 30),
-                (PlaylistLayout::Album, 30),
-                (PlaylistLayout::Duration, 5),
+                Column::new(vec![Field::Tag("Artist")], Width::Percent(25), Align::Left),
+                Column::new(vec![Field::Tag("Track")], Width::Fit, Align::Right),
+                Column::new(
+                    vec![Field::Tag("Title"), Field::Filename],
+                    Width::Percent(40),
+                    Align::Left,
+                ),
+                Column::new(vec![Field::Tag("Album")], Width::Percent(35), Align::Left),
+                Column::new(vec![Field::Duration], Width::Fit, Align::Right),
             ],
         }
     }
 }
 
 impl Config {
+    /// Reads the config file, falling back to the defaults if there is none,
+    /// then overrides the connection settings from the environment and the command line
     pub(crate) fn new() -> Result<Self, Box<dyn Error>> {
-        let config = Self::default();
+        let mut config = Self::from_file()?;
+        config.connection.apply_env()?;
+        config.connection.apply_args(env::args().skip(1))?;
 
         Ok(config)
     }
 
+    fn from_file() -> Result<Self, Box<dyn Error>> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        };
+
+        let config = toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Ok(config)
+    }
+
+    /// Path to the config file: `$XDG_CONFIG_HOME/empirust/config.toml`
+    /// or `~/.config/empirust/config.toml`
+    fn path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join("empirust").join("config.toml"))
+    }
+
+    pub(crate) const fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
     pub(crate) const fn styles(&self) -> &Styles {
         &self.styles
     }
@@ -54,18 +302,430 @@ impl Config {
         &self.keys
     }
 
-    pub(crate) fn playlist_layout(&self) -> &[(PlaylistLayout, u16)] {
+    pub(crate) const fn library(&self) -> &Library {
+        &self.library
+    }
+
+    pub(crate) const fn playback(&self) -> &Playback {
+        &self.playback
+    }
+
+    pub(crate) const fn undo(&self) -> &Undo {
+        &self.undo
+    }
+
+    pub(crate) const fn formats(&self) -> &Formats {
+        &self.formats
+    }
+
+    pub(crate) fn playlist_layout(&self) -> &[Column] {
         self.playlist_layout.as_ref()
     }
+
+    /// Changes a setting while running, `name` being its path in the config file like `keys.quit`
+    pub(crate) fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let unknown = || format!("unknown setting `{}`", name);
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid value for `{}`: {}", name, e);
+        let (section, field) = name.split_once('.').ok_or_else(unknown)?;
+        match (section, field) {
+            ("keys", action) => self.keys.bind(action, value)?,
+            ("playback", "seek_step") => {
+                self.playback.seek_step = value.parse().map_err(|e| invalid(&e))?;
+            }
+            ("library", "tag") => {
+                self.library.tag = LibraryTag::deserialize(StrDeserializer::new(value))
+                    .map_err(|e: de::value::Error| invalid(&e))?;
+            }
+            ("library", "sort_albums") => {
+                self.library.sort_albums = AlbumSort::deserialize(StrDeserializer::new(value))
+                    .map_err(|e: de::value::Error| invalid(&e))?;
+            }
+            ("undo", "depth") => {
+                self.undo.depth = value.parse().map_err(|e| invalid(&e))?;
+            }
+            ("formats", "now_playing") => {
+                self.formats.now_playing = value.parse().map_err(|e| invalid(&e))?;
+            }
+            ("formats", "window_title") => {
+                self.formats.window_title = value.parse().map_err(|e| invalid(&e))?;
+            }
+            ("styles", style) => {
+                /// Inline table of the style, as in the config file
+                #[derive(Deserialize)]
+                struct Wrapper {
+                    style: StyleDef,
+                }
+                let default = Styles::default()
+                    .get_mut(style)
+                    .copied()
+                    .ok_or_else(unknown)?;
+                let wrapper: Wrapper =
+                    toml::from_str(&format!("style = {}", value)).map_err(|e| invalid(&e))?;
+                let style = self.styles.get_mut(style).ok_or_else(unknown)?;
+                *style = default.patch(wrapper.style.into());
+            }
+            _ => return Err(unknown()),
+        }
+        Ok(())
+    }
+
+    /// Names of the settings `set` changes
+    pub(crate) fn settings() -> impl Iterator<Item = String> {
+        let keys = Keys::actions().map(|action| format!("keys.{}", action));
+        let styles = STYLES.iter().map(|style| format!("styles.{}", style));
+        [
+            "playback.seek_step",
+            "library.tag",
+            "library.sort_albums",
+            "undo.depth",
+            "formats.now_playing",
+            "formats.window_title",
+        ]
+        .into_iter()
+        .map(String::from)
+        .chain(styles)
+        .chain(keys)
+    }
+}
+
+fn deserialize_playlist_layout<'de, D>(deserializer: D) -> Result<Vec<Column>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    /// A field or its fallbacks, not untagged to keep the errors about the fields
+    struct Fields(Vec<Field>);
+
+    impl<'de> Deserialize<'de> for Fields {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct FieldsVisitor;
+
+            impl<'de> de::Visitor<'de> for FieldsVisitor {
+                type Value = Fields;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a field or a list of them")
+                }
+
+                fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                    let field = Field::deserialize(StrDeserializer::<E>::new(v))?;
+                    Ok(Fields(vec![field]))
+                }
+
+                fn visit_seq<A: de::SeqAccess<'de>>(
+                    self,
+                    mut seq: A,
+                ) -> Result<Self::Value, A::Error> {
+                    let mut fields = Vec::new();
+                    while let Some(field) = seq.next_element()? {
+                        fields.push(field);
+                    }
+                    if fields.is_empty() {
+                        return Err(de::Error::custom("a column needs at least one field"));
+                    }
+                    Ok(Fields(fields))
+                }
+            }
+
+            deserializer.deserialize_any(FieldsVisitor)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct ColumnDef {
+        column: Option<Fields>,
+        format: Option<Template>,
+        width: Width,
+        #[serde(default)]
+        align: Align,
+        title: Option<String>,
+    }
+
+    let columns = Vec::<ColumnDef>::deserialize(deserializer)?;
+    let percents: u16 = columns
+        .iter()
+        .filter_map(|column| match column.width {
+            Width::Percent(percent) => Some(percent),
+            _ => None,
+        })
+        .sum();
+    if percents > 100 {
+        return Err(de::Error::custom(
+            "sum of the column widths exceeds 100 percents",
+        ));
+    }
+
+    columns
+        .into_iter()
+        .map(|def| {
+            let mut column = match (def.column, def.format) {
+                (Some(fields), None) => Column::new(fields.0, def.width, def.align),
+                (None, Some(format)) => Column {
+                    content: Content::Format(format),
+                    title: String::new(),
+                    width: def.width,
+                    align: def.align,
+                },
+                _ => {
+                    return Err(de::Error::custom(
+                        "a column needs either `column` or `format`",
+                    ))
+                }
+            };
+            if let Some(title) = def.title {
+                column.title = title;
+            }
+            Ok(column)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Connection {
+    host: String,
+    port: u16,
+    socket: Option<PathBuf>,
+    password: Option<String>,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 6600,
+            socket: None,
+            password: None,
+        }
+    }
+}
+
+impl Connection {
+    fn apply_env(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Ok(host) = env::var("MPD_HOST") {
+            self.set_host(&host);
+        }
+        if let Ok(port) = env::var("MPD_PORT") {
+            self.port = port
+                .parse()
+                .map_err(|e| format!("MPD_PORT `{}`: {}", port, e))?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_args(&mut self, mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for `{}`", arg))
+            };
+            match arg.as_str() {
+                "--host" => self.set_host(&value()?),
+                "--port" => {
+                    let port = value()?;
+                    self.port = port
+                        .parse()
+                        .map_err(|e| format!("--port `{}`: {}", port, e))?;
+                }
+                "--socket" => self.socket = Some(expand_tilde(&value()?)),
+                _ => return Err(format!("unexpected argument `{}`\n\n{}", arg, USAGE).into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets host in the form of MPD_HOST: `[password@]host`, where host may be a socket path
+    fn set_host(&mut self, host: &str) {
+        let host = match host.split_once('@') {
+            Some((password, host)) if !password.is_empty() => {
+                self.password = Some(password.to_string());
+                host
+            }
+            _ => host,
+        };
+
+        if host.starts_with('/') || host.starts_with('~') {
+            self.socket = Some(expand_tilde(host));
+        } else {
+            self.host = host.to_string();
+            self.socket = None;
+        }
+    }
+
+    pub(crate) fn host(&self) -> &str {
+        self.host.as_ref()
+    }
+
+    pub(crate) const fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn socket(&self) -> Option<&PathBuf> {
+        self.socket.as_ref()
+    }
+
+    pub(crate) fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+}
+
+impl std::fmt::Display for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.socket {
+            Some(socket) => write!(f, "{}", socket.display()),
+            None => write!(f, "{}:{}", self.host, self.port),
+        }
+    }
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Tag listed in the first column of the library
+pub enum LibraryTag {
+    Artist,
+    AlbumArtist,
+    Genre,
+    Date,
+}
+
+impl LibraryTag {
+    /// Name of the tag as MPD knows it
+    pub(crate) const fn name(self) -> &'static str {
+        match self {
+            Self::Artist => "Artist",
+            Self::AlbumArtist => "AlbumArtist",
+            Self::Genre => "Genre",
+            Self::Date => "Date",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumSort {
+    Name,
+    /// By the earliest date of the album's songs
+    Date,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Library {
+    tag: LibraryTag,
+    sort_albums: AlbumSort,
+}
+
+impl Default for Library {
+    fn default() -> Self {
+        Self {
+            tag: LibraryTag::Artist,
+            sort_albums: AlbumSort::Name,
+        }
+    }
+}
+
+impl Library {
+    pub(crate) const fn tag(&self) -> LibraryTag {
+        self.tag
+    }
+
+    pub(crate) const fn sort_albums(&self) -> AlbumSort {
+        self.sort_albums
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Playback {
+    /// Seconds the seek keys move by
+    seek_step: u16,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self { seek_step: 5 }
+    }
+}
+
+impl Playback {
+    pub(crate) const fn seek_step(&self) -> u16 {
+        self.seek_step
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Undo {
+    /// Changes to the queue which can be undone, 0 turns undo off
+    depth: usize,
+}
+
+impl Default for Undo {
+    fn default() -> Self {
+        Self { depth: 50 }
+    }
+}
+
+impl Undo {
+    pub(crate) const fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Templates the playing song is shown with
+pub struct Formats {
+    now_playing: Template,
+    window_title: Template,
+}
+
+impl Default for Formats {
+    fn default() -> Self {
+        let template = |source: &str| source.parse().expect("default template is valid");
+        Self {
+            now_playing: template("{$(bold)%a$(/) - }{%t}|{%f}"),
+            window_title: template("{%a - }{%t}|{%f}"),
+        }
+    }
+}
+
+impl Formats {
+    pub(crate) const fn now_playing(&self) -> &Template {
+        &self.now_playing
+    }
+
+    pub(crate) const fn window_title(&self) -> &Template {
+        &self.window_title
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(from = "StylesFile")]
 pub struct Styles {
     tab_selected: Style,
     normal: Style,
     selected: Style,
     playing: Style,
+    marked: Style,
+    matched: Style,
     progress: Style,
+    mode_on: Style,
+    mode_off: Style,
+    mode_oneshot: Style,
+    /// Album headers of the grouped queue
+    group: Style,
+    /// Songs with a priority, played before the others in random mode
+    queued: Style,
 }
 
 impl Default for Styles {
@@ -75,15 +735,206 @@ impl Default for Styles {
             normal: Style::default().fg(Color::Reset).bg(Color::Reset),
             selected: Style::default().fg(Color::Black).bg(Color::Magenta),
             playing: Style::default().fg(Color::Cyan).bg(Color::Black),
+            marked: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            matched: Style::default().fg(Color::Black).bg(Color::Yellow),
             progress: Style::default()
                 .bg(Color::Black)
                 .fg(Color::Magenta)
                 .add_modifier(Modifier::BOLD),
+            mode_on: Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+            mode_off: Style::default().fg(Color::DarkGray),
+            mode_oneshot: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            group: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            queued: Style::default().fg(Color::Green),
+        }
+    }
+}
+
+/// Styles as they are written in the config file, each one patches the default
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StylesFile {
+    tab_selected: Option<StyleDef>,
+    normal: Option<StyleDef>,
+    selected: Option<StyleDef>,
+    playing: Option<StyleDef>,
+    marked: Option<StyleDef>,
+    matched: Option<StyleDef>,
+    progress: Option<StyleDef>,
+    mode_on: Option<StyleDef>,
+    mode_off: Option<StyleDef>,
+    mode_oneshot: Option<StyleDef>,
+    group: Option<StyleDef>,
+    queued: Option<StyleDef>,
+}
+
+impl From<StylesFile> for Styles {
+    fn from(file: StylesFile) -> Self {
+        let default = Self::default();
+        let patch =
+            |style: Style, def: Option<StyleDef>| def.map_or(style, |def| style.patch(def.into()));
+
+        Self {
+            tab_selected: patch(default.tab_selected, file.tab_selected),
+            normal: patch(default.normal, file.normal),
+            selected: patch(default.selected, file.selected),
+            playing: patch(default.playing, file.playing),
+            marked: patch(default.marked, file.marked),
+            matched: patch(default.matched, file.matched),
+            progress: patch(default.progress, file.progress),
+            mode_on: patch(default.mode_on, file.mode_on),
+            mode_off: patch(default.mode_off, file.mode_off),
+            mode_oneshot: patch(default.mode_oneshot, file.mode_oneshot),
+            group: patch(default.group, file.group),
+            queued: patch(default.queued, file.queued),
         }
     }
 }
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleDef {
+    #[serde(default, deserialize_with = "deserialize_color")]
+    fg: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    bg: Option<Color>,
+    #[serde(default)]
+    modifiers: Vec<ModifierDef>,
+}
+
+impl From<StyleDef> for Style {
+    fn from(def: StyleDef) -> Self {
+        let mut style = Self::default();
+        if let Some(fg) = def.fg {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = def.bg {
+            style = style.bg(bg);
+        }
+        def.modifiers
+            .into_iter()
+            .fold(style, |style, modifier| style.add_modifier(modifier.into()))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModifierDef {
+    Bold,
+    Dim,
+    Italic,
+    Underlined,
+    SlowBlink,
+    RapidBlink,
+    Reversed,
+    Hidden,
+    CrossedOut,
+}
+
+impl From<ModifierDef> for Modifier {
+    fn from(def: ModifierDef) -> Self {
+        match def {
+            ModifierDef::Bold => Self::BOLD,
+            ModifierDef::Dim => Self::DIM,
+            ModifierDef::Italic => Self::ITALIC,
+            ModifierDef::Underlined => Self::UNDERLINED,
+            ModifierDef::SlowBlink => Self::SLOW_BLINK,
+            ModifierDef::RapidBlink => Self::RAPID_BLINK,
+            ModifierDef::Reversed => Self::REVERSED,
+            ModifierDef::Hidden => Self::HIDDEN,
+            ModifierDef::CrossedOut => Self::CROSSED_OUT,
+        }
+    }
+}
+
+fn deserialize_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let color = String::deserialize(deserializer)?;
+    parse_color(&color).map(Some).map_err(de::Error::custom)
+}
+
+/// Parses a color name, `#rrggbb` or an index of the terminal's palette
+pub(crate) fn parse_color(color: &str) -> Result<Color, String> {
+    let parsed = match color.to_lowercase().replace(['-', ' '], "_").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "dark_gray" => Color::DarkGray,
+        "light_red" => Color::LightRed,
+        "light_green" => Color::LightGreen,
+        "light_yellow" => Color::LightYellow,
+        "light_blue" => Color::LightBlue,
+        "light_magenta" => Color::LightMagenta,
+        "light_cyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.len() == 7 && hex.starts_with('#') => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+            match (channel(1), channel(3), channel(5)) {
+                (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                _ => return Err(format!("invalid hex color `{}`", color)),
+            }
+        }
+        index => match index.parse::<u8>() {
+            Ok(index) => Color::Indexed(index),
+            Err(_) => return Err(format!("unknown color `{}`", color)),
+        },
+    };
+
+    Ok(parsed)
+}
+
+/// Names of the styles, as in the config file
+const STYLES: [&str; 12] = [
+    "tab_selected",
+    "normal",
+    "selected",
+    "playing",
+    "marked",
+    "matched",
+    "progress",
+    "mode_on",
+    "mode_off",
+    "mode_oneshot",
+    "group",
+    "queued",
+];
+
 impl Styles {
+    fn get_mut(&mut self, name: &str) -> Option<&mut Style> {
+        let style = match name {
+            "tab_selected" => &mut self.tab_selected,
+            "normal" => &mut self.normal,
+            "selected" => &mut self.selected,
+            "playing" => &mut self.playing,
+            "marked" => &mut self.marked,
+            "matched" => &mut self.matched,
+            "progress" => &mut self.progress,
+            "mode_on" => &mut self.mode_on,
+            "mode_off" => &mut self.mode_off,
+            "mode_oneshot" => &mut self.mode_oneshot,
+            "group" => &mut self.group,
+            "queued" => &mut self.queued,
+            _ => return None,
+        };
+        Some(style)
+    }
+
     pub(crate) const fn tab_selected(&self) -> Style {
         self.tab_selected
     }
@@ -100,106 +951,879 @@ impl Styles {
         self.playing
     }
 
+    pub(crate) const fn marked(&self) -> Style {
+        self.marked
+    }
+
+    pub(crate) const fn matched(&self) -> Style {
+        self.matched
+    }
+
     pub(crate) const fn progress(&self) -> Style {
         self.progress
     }
+
+    pub(crate) const fn mode_on(&self) -> Style {
+        self.mode_on
+    }
+
+    pub(crate) const fn mode_off(&self) -> Style {
+        self.mode_off
+    }
+
+    pub(crate) const fn mode_oneshot(&self) -> Style {
+        self.mode_oneshot
+    }
+
+    pub(crate) const fn group(&self) -> Style {
+        self.group
+    }
+
+    pub(crate) const fn queued(&self) -> Style {
+        self.queued
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Where a key works
+pub enum Scope {
+    Global,
+    /// Only on the tabs with these indices
+    Tabs(&'static [usize]),
 }
 
-#[derive(Debug)]
+impl Scope {
+    fn contains(self, tab: usize) -> bool {
+        match self {
+            Self::Global => true,
+            Self::Tabs(tabs) => tabs.contains(&tab),
+        }
+    }
+
+    /// Whether both work on some tab
+    fn overlaps(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Tabs(tabs), Self::Tabs(others)) => tabs.iter().any(|tab| others.contains(tab)),
+            _ => true,
+        }
+    }
+}
+
+/// Action a key can be bound to
+struct Action {
+    /// Name in the config file
+    name: &'static str,
+    /// Default bindings
+    keys: &'static [&'static str],
+    /// Command line the key runs
+    command: &'static str,
+    scope: Scope,
+    description: &'static str,
+}
+
+const ACTIONS: [Action; 50] = [
+    Action {
+        name: "quit",
+        keys: &["q"],
+        command: "quit",
+        scope: Scope::Global,
+        description: "Quit",
+    },
+    Action {
+        name: "switch_tab",
+        keys: &["tab"],
+        command: "tab",
+        scope: Scope::Global,
+        description: "Switch tab",
+    },
+    Action {
+        name: "toggle_pause",
+        keys: &["p"],
+        command: "pause",
+        scope: Scope::Global,
+        description: "Toggle pause",
+    },
+    Action {
+        name: "vol_down",
+        keys: &["left"],
+        command: "vol -5",
+        scope: Scope::Global,
+        description: "Volume down",
+    },
+    Action {
+        name: "vol_up",
+        keys: &["right"],
+        command: "vol +5",
+        scope: Scope::Global,
+        description: "Volume up",
+    },
+    Action {
+        name: "queue_next",
+        keys: &["j", "down"],
+        command: "down",
+        scope: Scope::Global,
+        description: "Move next",
+    },
+    Action {
+        name: "queue_prev",
+        keys: &["k", "up"],
+        command: "up",
+        scope: Scope::Global,
+        description: "Move back",
+    },
+    Action {
+        name: "switch_song",
+        keys: &["enter"],
+        command: "open",
+        scope: Scope::Global,
+        description: "Switch to song under cursor",
+    },
+    Action {
+        name: "delete_song",
+        keys: &["d"],
+        command: "delete",
+        scope: Scope::Tabs(&[QUEUE_TAB, PLAYLISTS_TAB]),
+        description: "Delete song under cursor",
+    },
+    Action {
+        name: "move_song_up",
+        keys: &["K"],
+        command: "move -1",
+        scope: Scope::Tabs(&[QUEUE_TAB, PLAYLISTS_TAB]),
+        description: "Move song under cursor up",
+    },
+    Action {
+        name: "move_song_down",
+        keys: &["J"],
+        command: "move +1",
+        scope: Scope::Tabs(&[QUEUE_TAB, PLAYLISTS_TAB]),
+        description: "Move song under cursor down",
+    },
+    Action {
+        name: "crop_queue",
+        keys: &["C"],
+        command: "crop",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Remove all songs but the playing one",
+    },
+    Action {
+        name: "clear_queue",
+        keys: &["c"],
+        command: "clear",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Clear queue",
+    },
+    Action {
+        name: "toggle_mark",
+        keys: &["space"],
+        command: "mark",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Mark song under cursor",
+    },
+    Action {
+        name: "visual_mode",
+        keys: &["v"],
+        command: "visual",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Start or end marking a range",
+    },
+    Action {
+        name: "clear_marks",
+        keys: &["esc"],
+        command: "unmark",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Unmark all songs",
+    },
+    Action {
+        name: "add_to_playlist",
+        keys: &["a"],
+        command: "addto",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Add marked songs to a playlist",
+    },
+    Action {
+        name: "priority_up",
+        keys: &["+"],
+        command: "priority +10",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Raise priority of marked songs",
+    },
+    Action {
+        name: "priority_down",
+        keys: &["-"],
+        command: "priority -10",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Lower priority of marked songs",
+    },
+    Action {
+        name: "parent_dir",
+        keys: &["backspace"],
+        command: "back",
+        scope: Scope::Tabs(&[BROWSE_TAB, LIBRARY_TAB, PLAYLISTS_TAB]),
+        description: "Go to parent directory or left column",
+    },
+    Action {
+        name: "add_to_queue",
+        keys: &["A"],
+        command: "add",
+        scope: Scope::Global,
+        description: "Add to queue, or move to its end",
+    },
+    Action {
+        name: "insert_next",
+        keys: &["i"],
+        command: "insert",
+        scope: Scope::Global,
+        description: "Insert or move after playing song",
+    },
+    Action {
+        name: "replace_queue",
+        keys: &["R"],
+        command: "replace",
+        scope: Scope::Tabs(&[BROWSE_TAB, LIBRARY_TAB, PLAYLISTS_TAB, SEARCH_TAB]),
+        description: "Replace queue and play",
+    },
+    Action {
+        name: "rename_playlist",
+        keys: &["r"],
+        command: "rename",
+        scope: Scope::Tabs(&[PLAYLISTS_TAB]),
+        description: "Rename playlist",
+    },
+    Action {
+        name: "save_queue",
+        keys: &["S"],
+        command: "save",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Save queue or marked songs as playlist",
+    },
+    Action {
+        name: "sort_queue",
+        keys: &["o"],
+        command: "sort",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Sort queue by album artist, date, album, disc and track",
+    },
+    Action {
+        name: "group_queue",
+        keys: &["O"],
+        command: "group",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Toggle album headers in the queue",
+    },
+    Action {
+        name: "undo",
+        keys: &["u"],
+        command: "undo",
+        scope: Scope::Global,
+        description: "Undo the last change to the queue",
+    },
+    Action {
+        name: "redo",
+        keys: &["ctrl-r"],
+        command: "redo",
+        scope: Scope::Global,
+        description: "Redo the last undone change to the queue",
+    },
+    Action {
+        name: "filter_queue",
+        keys: &["/"],
+        command: "filter",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Filter the queue",
+    },
+    Action {
+        name: "search",
+        keys: &["/"],
+        command: "search",
+        scope: Scope::Tabs(&[SEARCH_TAB]),
+        description: "Edit the search query",
+    },
+    Action {
+        name: "search_scope",
+        keys: &["T"],
+        command: "scope",
+        scope: Scope::Tabs(&[SEARCH_TAB]),
+        description: "Change tag searched in",
+    },
+    Action {
+        name: "search_exact",
+        keys: &["E"],
+        command: "exact",
+        scope: Scope::Tabs(&[SEARCH_TAB]),
+        description: "Toggle exact matching",
+    },
+    Action {
+        name: "add_all",
+        keys: &["*"],
+        command: "addall",
+        scope: Scope::Tabs(&[SEARCH_TAB]),
+        description: "Add all results to queue",
+    },
+    Action {
+        name: "next_match",
+        keys: &["n"],
+        command: "match next",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Jump to the next song matching the filter",
+    },
+    Action {
+        name: "prev_match",
+        keys: &["N"],
+        command: "match prev",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Jump to the previous song matching the filter",
+    },
+    Action {
+        name: "toggle_filter",
+        keys: &["F"],
+        command: "only-matches",
+        scope: Scope::Tabs(&[QUEUE_TAB]),
+        description: "Show only the songs matching the filter",
+    },
+    Action {
+        name: "fuzzy_find",
+        keys: &["P"],
+        command: "find",
+        scope: Scope::Global,
+        description: "Find a song in the library",
+    },
+    Action {
+        name: "next_song",
+        keys: &[">"],
+        command: "next",
+        scope: Scope::Global,
+        description: "Play the next song",
+    },
+    Action {
+        name: "prev_song",
+        keys: &["<"],
+        command: "prev",
+        scope: Scope::Global,
+        description: "Play the previous song",
+    },
+    Action {
+        name: "stop",
+        keys: &["s"],
+        command: "stop",
+        scope: Scope::Global,
+        description: "Stop playing",
+    },
+    Action {
+        name: "seek_forward",
+        keys: &["f"],
+        command: "seek forward",
+        scope: Scope::Global,
+        description: "Seek forward by the seek step",
+    },
+    Action {
+        name: "seek_backward",
+        keys: &["b"],
+        command: "seek backward",
+        scope: Scope::Global,
+        description: "Seek backward by the seek step",
+    },
+    Action {
+        name: "toggle_repeat",
+        keys: &["Z"],
+        command: "repeat",
+        scope: Scope::Global,
+        description: "Toggle the repeat mode",
+    },
+    Action {
+        name: "toggle_random",
+        keys: &["z"],
+        command: "random",
+        scope: Scope::Global,
+        description: "Toggle the random mode",
+    },
+    Action {
+        name: "cycle_single",
+        keys: &["y"],
+        command: "single",
+        scope: Scope::Global,
+        description: "Switch the single mode between off, on and oneshot",
+    },
+    Action {
+        name: "cycle_consume",
+        keys: &["Y"],
+        command: "consume",
+        scope: Scope::Global,
+        description: "Switch the consume mode between off, on and oneshot",
+    },
+    Action {
+        name: "crossfade",
+        keys: &["X"],
+        command: "crossfade",
+        scope: Scope::Global,
+        description: "Set the crossfade",
+    },
+    Action {
+        name: "help",
+        keys: &["?"],
+        command: "help",
+        scope: Scope::Global,
+        description: "Toggle this help",
+    },
+    Action {
+        name: "command_line",
+        keys: &[":"],
+        command: "command-line",
+        scope: Scope::Global,
+        description: "Type in a command",
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Key pressed along with the modifiers held
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Shift is left out of chars since it is already part of them, and tab with shift is backtab
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers.intersection(KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let shift = modifiers.contains(KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if shift => KeyCode::Char(c.to_ascii_uppercase()),
+            KeyCode::Tab if shift => KeyCode::BackTab,
+            code => code,
+        };
+        if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// Key named like `ctrl-d`, `shift-g`, `alt-enter` or `F5`
+    fn parse(name: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = name;
+        // a lone `-` is a key too
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+        Some(Self::new(Self::code(rest)?, modifiers))
+    }
+
+    fn code(name: &str) -> Option<KeyCode> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(KeyCode::Char(c));
+        }
+
+        let name = name.to_ascii_lowercase();
+        if let Some((_, code)) = KEY_NAMES.iter().find(|(key, _)| *key == name) {
+            return Some(*code);
+        }
+        let n = name.strip_prefix('f')?.parse().ok()?;
+        (1..=12).contains(&n).then_some(KeyCode::F(n))
+    }
+}
+
+/// Keys which have a name instead of being typed as a char, besides `F1` to `F12`
+const KEY_NAMES: [(&str, KeyCode); 16] = [
+    ("space", KeyCode::Char(' ')),
+    ("backspace", KeyCode::Backspace),
+    ("enter", KeyCode::Enter),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("esc", KeyCode::Esc),
+];
+
+/// Whether a word which is not a key looks like a misspelt name of one, like `pgdown`, `F13` or `ent`
+fn like_key_name(word: &str) -> bool {
+    let word = word.to_ascii_lowercase();
+    let function_key = word
+        .strip_prefix('f')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    function_key
+        || KEY_NAMES
+            .iter()
+            .any(|(name, _)| name.starts_with(&word) || (name.len() > 2 && word.contains(name)))
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+/// Keys pressed one after another, from a binding like `gg` or `ctrl-w j`.
+/// A word which is not the name of a key is typed char by char, unless it looks like a misspelt one
+fn parse_sequence(binding: &str) -> Option<Vec<Key>> {
+    let mut keys = Vec::new();
+    for word in binding.split_whitespace() {
+        match Key::parse(word) {
+            Some(key) => keys.push(key),
+            None if !word.contains('-')
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_graphic() || c.is_alphabetic())
+                && !like_key_name(word) =>
+            {
+                keys.extend(
+                    word.chars()
+                        .map(|c| Key::new(KeyCode::Char(c), KeyModifiers::NONE)),
+                );
+            }
+            None => return None,
+        }
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// What the keys pressed so far run
+pub enum Lookup {
+    Command(&'static str),
+    /// The keys start a sequence, the next ones tell which
+    Pending,
+    None,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "KeyNames")]
 pub struct Keys {
-    quit: KeyCode,
-    switch_tab: KeyCode,
-    toggle_pause: KeyCode,
-    vol_down: KeyCode,
-    vol_up: KeyCode,
-    queue_next: KeyCode,
-    queue_prev: KeyCode,
-    switch_song: KeyCode,
+    /// Sequences bound to every action, in the order of `ACTIONS`
+    bindings: Vec<Vec<Vec<Key>>>,
+    /// Bindings as they are written, for the help
+    names: Vec<Vec<String>>,
     keys: Vec<Vec<String>>,
 }
 
 impl Default for Keys {
     fn default() -> Self {
-        let mut keys: Vec<Vec<String>> = Vec::new();
+        KeyNames::default()
+            .try_into()
+            .expect("default keys are valid")
+    }
+}
 
-        Self {
-            quit: Self::gen_key_and_desc(&mut keys, "q", "Quit"),
-            switch_tab: Self::gen_key_and_desc(&mut keys, "tab", "Switch tab"),
-            toggle_pause: Self::gen_key_and_desc(&mut keys, "p", "Toggle pause"),
-            vol_down: Self::gen_key_and_desc(&mut keys, "left", "Volume down"),
-            vol_up: Self::gen_key_and_desc(&mut keys, "right", "Volume up"),
-            queue_next: Self::gen_key_and_desc(&mut keys, "j", "Move next"),
-            queue_prev: Self::gen_key_and_desc(&mut keys, "k", "Move back"),
-            switch_song: Self::gen_key_and_desc(&mut keys, "enter", "Switch to song under cursor"),
-            keys,
+#[derive(Deserialize)]
+#[serde(untagged)]
+/// One binding or several of them
+enum Bindings {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Bindings {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Self::One(binding) => vec![binding],
+            Self::Many(bindings) => bindings,
+        }
+    }
+}
+
+/// Keys as they are written in the config file, by the name of their action
+#[derive(Default, Deserialize)]
+#[serde(transparent)]
+struct KeyNames(HashMap<String, Bindings>);
+
+impl TryFrom<KeyNames> for Keys {
+    type Error = String;
+
+    fn try_from(mut names: KeyNames) -> Result<Self, Self::Error> {
+        if let Some(name) = names
+            .0
+            .keys()
+            .find(|name| !ACTIONS.iter().any(|action| action.name == *name))
+        {
+            return Err(format!("unknown action `{}`", name));
         }
+
+        let names: Vec<Vec<String>> = ACTIONS
+            .iter()
+            .map(|action| match names.0.remove(action.name) {
+                Some(bindings) => bindings.into_vec(),
+                None => action.keys.iter().map(ToString::to_string).collect(),
+            })
+            .collect();
+        let bindings = ACTIONS
+            .iter()
+            .zip(&names)
+            .map(|(action, names)| Self::parse_bindings(action.name, names))
+            .collect::<Result<_, _>>()?;
+
+        let keys = Self {
+            bindings,
+            keys: Self::help(&names),
+            names,
+        };
+        keys.check_conflicts()?;
+        Ok(keys)
     }
 }
 
 impl Keys {
-    fn gen_key_and_desc(keys: &mut Vec<Vec<String>>, key: &str, desc: &str) -> KeyCode {
-        let cell = vec![key.to_string(), desc.to_string()];
-        keys.push(cell);
-        Self::to_keycode(key)
+    fn parse_bindings(action: &str, names: &[String]) -> Result<Vec<Vec<Key>>, String> {
+        names
+            .iter()
+            .map(|name| {
+                parse_sequence(name)
+                    .ok_or_else(|| format!("unknown key `{}` for `{}`", name, action))
+            })
+            .collect()
     }
 
-    fn to_keycode(key: &str) -> KeyCode {
-        if key.len() == 1 {
-            KeyCode::Char(key.chars().next().unwrap())
+    /// Rows of the help: the bindings of every action and what it does
+    fn help(names: &[Vec<String>]) -> Vec<Vec<String>> {
+        ACTIONS
+            .iter()
+            .zip(names)
+            .map(|(action, names)| vec![names.join(", "), action.description.to_string()])
+            .collect()
+    }
+
+    /// Reports bindings of actions working on the same tab which are the same
+    /// or the start of one another, since only one of them could ever run
+    fn check_conflicts(&self) -> Result<(), String> {
+        let bindings: Vec<(&Action, &Vec<Key>, &String)> = ACTIONS
+            .iter()
+            .zip(self.bindings.iter().zip(&self.names))
+            .flat_map(|(action, (bindings, names))| {
+                bindings
+                    .iter()
+                    .zip(names)
+                    .map(move |(keys, name)| (action, keys, name))
+            })
+            .collect();
+
+        let conflicts: Vec<String> = bindings
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| bindings[i + 1..].iter().map(move |b| (a, b)))
+            .filter(|((a, a_keys, _), (b, b_keys, _))| {
+                a.name != b.name
+                    && a.scope.overlaps(b.scope)
+                    && (a_keys.starts_with(b_keys) || b_keys.starts_with(a_keys))
+            })
+            .map(|((a, _, a_name), (b, _, b_name))| {
+                format!(
+                    "`{}` of `{}` conflicts with `{}` of `{}`",
+                    a_name, a.name, b_name, b.name
+                )
+            })
+            .collect();
+
+        if conflicts.is_empty() {
+            Ok(())
         } else {
-            match key {
-                "backspace" => KeyCode::Backspace,
-                "enter" => KeyCode::Enter,
-                "left" => KeyCode::Left,
-                "right" => KeyCode::Right,
-                "up" => KeyCode::Up,
-                "down" => KeyCode::Down,
-                "home" => KeyCode::Home,
-                "end" => KeyCode::End,
-                "pageup" => KeyCode::PageUp,
-                "tab" => KeyCode::Tab,
-                "backtab" => KeyCode::BackTab,
-                "delete" => KeyCode::Delete,
-                "insert" => KeyCode::Insert,
-                "esc" => KeyCode::Esc,
-                _ => KeyCode::Null,
+            Err(conflicts.join(", "))
+        }
+    }
+
+    /// What the keys pressed one after another run on the tab
+    pub(crate) fn lookup(&self, keys: &[Key], tab: usize) -> Lookup {
+        let mut pending = false;
+        for (action, bindings) in ACTIONS.iter().zip(&self.bindings) {
+            if !action.scope.contains(tab) {
+                continue;
+            }
+            for binding in bindings {
+                if binding == keys {
+                    return Lookup::Command(action.command);
+                }
+                pending |= binding.starts_with(keys);
             }
         }
+        if pending {
+            Lookup::Pending
+        } else {
+            Lookup::None
+        }
     }
 
-    pub(crate) const fn quit(&self) -> KeyCode {
-        self.quit
+    /// Whether the key alone is bound to the action
+    pub(crate) fn matches(&self, name: &str, key: Key) -> bool {
+        ACTIONS
+            .iter()
+            .position(|action| action.name == name)
+            .is_some_and(|i| self.bindings[i].iter().any(|binding| binding == &[key]))
     }
 
-    pub(crate) const fn switch_tab(&self) -> KeyCode {
-        self.switch_tab
+    /// Binds the action to other keys: a binding, or a list of them like `["q", "ctrl-c"]`
+    pub(crate) fn bind(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let i = ACTIONS
+            .iter()
+            .position(|action| action.name == name)
+            .ok_or_else(|| format!("unknown action `{}`", name))?;
+        let names = if value.starts_with('[') {
+            #[derive(Deserialize)]
+            struct Wrapper {
+                keys: Vec<String>,
+            }
+            let wrapper: Wrapper =
+                toml::from_str(&format!("keys = {}", value)).map_err(|e| e.to_string())?;
+            wrapper.keys
+        } else {
+            vec![value.to_string()]
+        };
+        let bindings = Self::parse_bindings(name, &names)?;
+
+        let old_bindings = std::mem::replace(&mut self.bindings[i], bindings);
+        let old_names = std::mem::replace(&mut self.names[i], names);
+        if let Err(e) = self.check_conflicts() {
+            self.bindings[i] = old_bindings;
+            self.names[i] = old_names;
+            return Err(e);
+        }
+        self.keys = Self::help(&self.names);
+        Ok(())
     }
 
-    pub(crate) const fn toggle_pause(&self) -> KeyCode {
-        self.toggle_pause
+    /// Names of the actions keys can be bound to
+    pub(crate) fn actions() -> impl Iterator<Item = &'static str> {
+        ACTIONS.iter().map(|action| action.name)
     }
 
-    pub(crate) const fn vol_down(&self) -> KeyCode {
-        self.vol_down
+    pub(crate) fn keys(&self) -> &[Vec<String>] {
+        self.keys.as_ref()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
 
-    pub(crate) const fn vol_up(&self) -> KeyCode {
-        self.vol_up
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
     }
 
-    pub(crate) const fn queue_next(&self) -> KeyCode {
-        self.queue_next
+    fn chars(word: &str) -> Vec<Key> {
+        word.chars()
+            .map(|c| key(KeyCode::Char(c), KeyModifiers::NONE))
+            .collect()
     }
 
-    pub(crate) const fn queue_prev(&self) -> KeyCode {
-        self.queue_prev
+    fn keys(toml: &str) -> Result<Keys, String> {
+        toml::from_str::<KeyNames>(toml).unwrap().try_into()
     }
 
-    pub(crate) const fn switch_song(&self) -> KeyCode {
-        self.switch_song
+    #[test]
+    fn parses_keys_with_modifiers() {
+        let none = KeyModifiers::NONE;
+        assert_eq!(Key::parse("j"), Some(key(KeyCode::Char('j'), none)));
+        assert_eq!(Key::parse("-"), Some(key(KeyCode::Char('-'), none)));
+        assert_eq!(Key::parse("Enter"), Some(key(KeyCode::Enter, none)));
+        assert_eq!(Key::parse("F12"), Some(key(KeyCode::F(12), none)));
+        assert_eq!(
+            Key::parse("ctrl-alt-d"),
+            Some(key(
+                KeyCode::Char('d'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!(
+            Key::parse("ctrl--"),
+            Some(key(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
     }
 
-    pub(crate) fn keys(&self) -> &[Vec<String>] {
-        self.keys.as_ref()
+    #[test]
+    fn shift_is_part_of_chars_and_backtab() {
+        let none = KeyModifiers::NONE;
+        assert_eq!(Key::parse("shift-g"), Key::parse("G"));
+        assert_eq!(Key::parse("shift-g"), Some(key(KeyCode::Char('G'), none)));
+        assert_eq!(Key::parse("shift-tab"), Some(key(KeyCode::BackTab, none)));
+        assert_eq!(
+            Key::parse("shift-up"),
+            Some(key(KeyCode::Up, KeyModifiers::SHIFT))
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_keys() {
+        assert_eq!(Key::parse("F13"), None);
+        assert_eq!(Key::parse("pgdown"), None);
+        assert_eq!(Key::parse("hyper-j"), None);
+    }
+
+    #[test]
+    fn parses_sequences() {
+        assert_eq!(parse_sequence("gg"), Some(chars("gg")));
+        assert_eq!(parse_sequence("ZZ"), Some(chars("ZZ")));
+        assert_eq!(
+            parse_sequence("ctrl-w j"),
+            Some(vec![
+                key(KeyCode::Char('w'), KeyModifiers::CONTROL),
+                key(KeyCode::Char('j'), KeyModifiers::NONE)
+            ])
+        );
+        assert_eq!(
+            parse_sequence("g enter"),
+            Some(vec![
+                key(KeyCode::Char('g'), KeyModifiers::NONE),
+                key(KeyCode::Enter, KeyModifiers::NONE)
+            ])
+        );
+        assert_eq!(parse_sequence("  "), None);
+    }
+
+    #[test]
+    fn refuses_misspelt_key_names_as_sequences() {
+        for binding in [
+            "pgdown", "F13", "f0", "Enter2", "ent", "pageu", "ctrl-ww", "g\u{7}",
+        ] {
+            assert_eq!(parse_sequence(binding), None, "{}", binding);
+        }
+        assert_eq!(
+            keys(r#"queue_next = "pgdown""#).err().as_deref(),
+            Some("unknown key `pgdown` for `queue_next`")
+        );
+    }
+
+    #[test]
+    fn finds_conflicting_bindings() {
+        assert!(Keys::try_from(KeyNames::default()).is_ok());
+        assert_eq!(
+            keys(r#"quit = "j""#).err().as_deref(),
+            Some("`j` of `quit` conflicts with `j` of `queue_next`")
+        );
+        // a binding starting another one on the same tab never runs
+        assert!(keys(r#"quit = ["q", "pp"]"#)
+            .unwrap_err()
+            .contains("`pp` of `quit` conflicts with `p` of"));
+        // the same keys are fine on tabs which do not overlap
+        assert!(keys(r#"rename_playlist = "s""#).is_err());
+        assert!(keys(r#"rename_playlist = "o""#).is_ok());
+    }
+
+    #[test]
+    fn default_keys_run_valid_commands() {
+        for action in &ACTIONS {
+            if let Err(e) = action.command.parse::<Command>() {
+                panic!("`{}` runs `{}`: {}", action.name, action.command, e);
+            }
+        }
+
+        let keys = Keys::default();
+        let slash = |tab| match keys.lookup(&chars("/"), tab) {
+            Lookup::Command(command) => command.parse::<Command>().unwrap(),
+            _ => panic!("`/` does nothing on tab {}", tab),
+        };
+        assert_eq!(slash(QUEUE_TAB), Command::Filter(None));
+        assert_eq!(slash(SEARCH_TAB), Command::Search(None));
     }
 }
@@ -0,0 +1,26 @@
+//! Merges everything the main loop waits for into one channel
+
+use crossterm::event::{self, Event};
+use mpd::Subsystem;
+use std::{io, sync::mpsc::Sender, thread};
+
+/// Something which happened while the main loop was waiting
+pub enum Message {
+    /// Key press, mouse event or resize of the terminal
+    Terminal(io::Result<Event>),
+    /// Subsystems MPD reported as changed
+    Idle(Vec<Subsystem>),
+    /// Results of the fuzzy finder arrived
+    Finder,
+}
+
+/// Spawns a thread which reads the events of the terminal and sends them over the channel
+pub fn spawn_terminal(tx: Sender<Message>) {
+    thread::spawn(move || loop {
+        let event = event::read();
+        let failed = event.is_err();
+        if tx.send(Message::Terminal(event)).is_err() || failed {
+            return;
+        }
+    });
+}
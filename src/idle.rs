@@ -0,0 +1,44 @@
+//! Listens for MPD's idle notifications
+
+use crate::{config::Connection, events::Message, mpd::connect};
+use mpd::{Idle, Subsystem};
+use std::{sync::mpsc::Sender, thread, time::Duration};
+
+/// Subsystems the UI is interested in
+pub const SUBSYSTEMS: [Subsystem; 7] = [
+    Subsystem::Player,
+    Subsystem::Queue,
+    Subsystem::Mixer,
+    Subsystem::Options,
+    Subsystem::Database,
+    Subsystem::Playlist,
+    Subsystem::Output,
+];
+
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Spawns a thread which runs `idle` on its own connection and sends changed subsystems over the channel.
+/// After a reconnection every subsystem is sent, since changes could have been missed meanwhile
+pub fn spawn(connection: Connection, tx: Sender<Message>) {
+    thread::spawn(move || {
+        let mut reconnected = false;
+        loop {
+            let Ok(mut client) = connect(&connection) else {
+                reconnected = true;
+                thread::sleep(RETRY_DELAY);
+                continue;
+            };
+
+            if reconnected && tx.send(Message::Idle(SUBSYSTEMS.to_vec())).is_err() {
+                return;
+            }
+
+            while let Ok(changed) = client.wait(&SUBSYSTEMS) {
+                if tx.send(Message::Idle(changed)).is_err() {
+                    return;
+                }
+            }
+            reconnected = true;
+        }
+    });
+}
@@ -1,70 +1,363 @@
 //! Manages input keys
 
 use crate::{
-    config::Config,
-    mpd::Mpd,
-    ui::{app::App, draw::draw},
+    command::{self, Command},
+    config::{Config, Key, Lookup},
+    events::{self, Message},
+    idle,
+    mpd::{AddMode, Mpd},
+    ui::{
+        app::{App, QUEUE_TAB},
+        draw::{draw, draw_error},
+        history::History,
+        prompt::Prompt,
+    },
 };
-use crossterm::event::{self, Event, KeyCode};
-use std::time::{Duration, Instant};
-use tui::{backend::Backend, Terminal};
+use crossterm::{
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+    terminal::SetTitle,
+};
+use mpd::{State, Subsystem};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+use tui::{backend::Backend, layout::Rect, Terminal};
+
+/// Longest time between two keys of a sequence
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How many songs a notch of the mouse wheel scrolls by
+const SCROLL_LINES: isize = 3;
 
 pub fn input<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
     mut client: Mpd,
-    config: &Config,
-) -> crossterm::Result<()> {
-    let mut last_tick = Instant::now();
-    let quit = config.keys().quit();
-    let switch_tab = config.keys().switch_tab();
-    let toggle_pause = config.keys().toggle_pause();
-    let vol_down = config.keys().vol_down();
-    let vol_up = config.keys().vol_up();
-    let queue_next = config.keys().queue_next();
-    let queue_prev = config.keys().queue_prev();
-    let switch_song = config.keys().switch_song();
+    mut config: Config,
+) -> std::io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    events::spawn_terminal(tx.clone());
+    idle::spawn(config.connection().clone(), tx.clone());
+    app.finder_mut().notify(tx);
+    // keys of a sequence pressed so far
+    let mut pending: Vec<Key> = Vec::new();
+    let mut pending_at = Instant::now();
+    let mut window_title = String::new();
     loop {
-        // draw ui
-        terminal.draw(|f| draw(f, &mut app, config, &client))?;
-
-        let timeout = app
-            .tick_rate()
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_secs(0));
-
-        // catch input
-        if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('?') {
-                    app.show_popup = !app.show_popup;
+        terminal.draw(|f| draw(f, &mut app, &config, &client))?;
+        set_window_title(&mut window_title, &config, &client)?;
+
+        // sleep until something happens, handling all that arrived before drawing again
+        let first = match rx.recv_timeout(wait_time(&app, &client)) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        for message in first.into_iter().chain(rx.try_iter()) {
+            match message {
+                Message::Terminal(event) => {
+                    let quit = handle_event(
+                        &mut app,
+                        &mut client,
+                        &mut config,
+                        event?,
+                        &mut pending,
+                        &mut pending_at,
+                    );
+                    if quit {
+                        return Ok(());
+                    }
+                }
+                Message::Idle(changed) => idle_changed(&mut app, &mut client, &changed),
+                Message::Finder => {
+                    app.finder_mut().poll();
+                }
+            }
+        }
+
+        let selected_id = app.selected_id(&client);
+        client.tick();
+        app.update_queue(&client, selected_id);
+    }
+}
+
+/// How long to wait for a message before the screen or the connection need attention anyway.
+/// The progress bar, the reconnection countdown and errors change on their own
+fn wait_time(app: &App, client: &Mpd) -> Duration {
+    let changing =
+        client.status().state == State::Play || !client.connected() || client.error().is_some();
+    if changing {
+        client.tick_in().min(app.tick_rate())
+    } else {
+        client.tick_in()
+    }
+}
+
+/// Refreshes what was changed on MPD's side
+fn idle_changed(app: &mut App, client: &mut Mpd, changed: &[Subsystem]) {
+    let selected_id = app.selected_id(client);
+    client.update(changed);
+    app.update_queue(client, selected_id);
+    if changed.contains(&Subsystem::Database) {
+        app.browser_mut().invalidate(client);
+        app.library_mut().invalidate(client);
+        app.finder_mut().invalidate();
+    }
+    if changed.contains(&Subsystem::Playlist) {
+        app.playlists_mut().load(client);
+    }
+}
+
+/// Handles a key press or a mouse event. Returns whether it is to quit
+fn handle_event(
+    app: &mut App,
+    client: &mut Mpd,
+    config: &mut Config,
+    event: Event,
+    pending: &mut Vec<Key>,
+    pending_at: &mut Instant,
+) -> bool {
+    let keys = config.keys();
+    if let (Event::Key(key), true) = (&event, app.command_line().is_open()) {
+        if let Some(line) = command_line_key(app, client, *key) {
+            if run(app, client, config, &line) {
+                return true;
+            }
+        }
+    } else if let (Event::Key(key), true) = (&event, app.prompt().is_some()) {
+        prompt_key(app, client, *key);
+    } else if let (Event::Key(key), true) = (&event, app.playlist_chooser_mut().is_some()) {
+        // the chooser takes over the keys while it is open
+        let pressed = Key::from(*key);
+        match key.code {
+            _ if keys.matches("queue_next", pressed) => {
+                app.playlist_chooser_move(client, 1);
+            }
+            _ if keys.matches("queue_prev", pressed) => {
+                app.playlist_chooser_move(client, -1);
+            }
+            _ if keys.matches("switch_song", pressed) => {
+                app.confirm_playlist_chooser(client);
+            }
+            KeyCode::Esc => app.close_playlist_chooser(),
+            _ if keys.matches("quit", pressed) => app.close_playlist_chooser(),
+            _ => (),
+        }
+    } else if let (Event::Key(key), true) = (&event, app.finder_mut().is_open()) {
+        finder_key(app, client, *key);
+    } else if let (Event::Key(key), true) = (&event, app.search_editing()) {
+        match key.code {
+            KeyCode::Enter => app.search_mut().run(client),
+            KeyCode::Esc => app.search_mut().stop_editing(),
+            _ if keys.matches("switch_tab", Key::from(*key)) => app.tab_next(client),
+            _ => {
+                app.search_mut().input_mut().handle_key(*key);
+            }
+        }
+    } else if let (Event::Key(key), true) = (&event, app.filter_editing()) {
+        match key.code {
+            KeyCode::Enter => app.accept_filter(),
+            KeyCode::Esc => app.cancel_filter(),
+            _ => {
+                if app.filter_mut().input_mut().handle_key(*key) {
+                    app.update_filter(client);
+                }
+            }
+        }
+    } else if let Event::Mouse(mouse) = event {
+        let popup = app.prompt().is_some()
+            || app.playlist_chooser_mut().is_some()
+            || app.finder_mut().is_open();
+        if !popup {
+            mouse_event(app, client, config, mouse);
+        }
+    } else if let Event::Key(key) = event {
+        if pending_at.elapsed() > SEQUENCE_TIMEOUT {
+            pending.clear();
+        }
+        pending.push(Key::from(key));
+        *pending_at = Instant::now();
+        let mut lookup = keys.lookup(pending, app.tab_index());
+        if matches!(lookup, Lookup::None) && pending.len() > 1 {
+            // the sequence broke off, the last key may start another one
+            pending.drain(..pending.len() - 1);
+            lookup = keys.lookup(pending, app.tab_index());
+        }
+        match lookup {
+            Lookup::Command(line) => {
+                pending.clear();
+                if run(app, client, config, line) {
+                    return true;
                 }
-                match key.code {
-                    code if code == queue_next => app.next(),
-                    code if code == queue_prev => app.previous(),
-                    code if code == vol_down => change_volume(&mut client, -5),
-                    code if code == vol_up => change_volume(&mut client, 5),
-                    code if code == switch_tab => app.tab_next(),
-                    code if code == toggle_pause => client.client_mut().toggle_pause().unwrap(),
-                    code if code == switch_song => app.switch(&mut client),
-                    code if code == quit => return Ok(()),
-                    _ => (),
+            }
+            Lookup::Pending => (),
+            // the digits seek to 0%, 10%, ... 90% of the song
+            Lookup::None => {
+                pending.clear();
+                if let KeyCode::Char(digit @ '0'..='9') = key.code {
+                    let tenths = digit.to_digit(10).unwrap_or(0);
+                    client.seek_ratio(f64::from(tenths) / 10.0);
                 }
             }
         }
-        if last_tick.elapsed() >= app.tick_rate() {
-            client.update();
-            last_tick = Instant::now();
+    }
+    false
+}
+
+/// Shows an error in place of the UI until any key is pressed
+pub fn error_screen<B: Backend>(terminal: &mut Terminal<B>, message: &str) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|f| draw_error(f, message))?;
+
+        if let Event::Key(_) = event::read()? {
+            return Ok(());
+        }
+    }
+}
+
+/// Parses and runs a command line, errors being shown like the ones of MPD.
+/// Returns whether the command is to quit
+fn run(app: &mut App, client: &mut Mpd, config: &mut Config, line: &str) -> bool {
+    let result = line.parse::<Command>().and_then(|command| match command {
+        Command::Quit => Ok(true),
+        command => command::run(app, client, config, command).map(|()| false),
+    });
+    result.unwrap_or_else(|e| {
+        client.report(e);
+        false
+    })
+}
+
+/// Keys of the command line: enter runs the line, tab completes it, up and down go through the history.
+/// Returns the line once it is to be run
+fn command_line_key(app: &mut App, client: &mut Mpd, key: KeyEvent) -> Option<String> {
+    let command_line = app.command_line_mut();
+    match key.code {
+        KeyCode::Enter => return Some(command_line.take()).filter(|line| !line.is_empty()),
+        KeyCode::Esc => command_line.close(),
+        KeyCode::Tab => command_line.complete(|line| command::complete(line, client)),
+        KeyCode::Up => command_line.history_prev(),
+        KeyCode::Down => command_line.history_next(),
+        _ => command_line.handle_key(key),
+    }
+    None
+}
+
+/// Answers the prompt: yes/no ones with `y` and `n`, text ones by typing
+fn prompt_key(app: &mut App, client: &mut Mpd, key: KeyEvent) {
+    let is_text = app.prompt().is_some_and(|prompt| prompt.input().is_some());
+    match key.code {
+        KeyCode::Enter => app.confirm_prompt(client),
+        KeyCode::Esc => app.close_prompt(),
+        KeyCode::Char('y') if !is_text => app.confirm_prompt(client),
+        KeyCode::Char('n') if !is_text => app.close_prompt(),
+        _ => {
+            if let Some(input) = app.prompt_mut().and_then(Prompt::input_mut) {
+                input.handle_key(key);
+            }
+        }
+    }
+}
+
+/// Keys of the fuzzy finder: enter plays the song picked, tab adds it to the queue
+fn finder_key(app: &mut App, client: &mut Mpd, key: KeyEvent) {
+    let mode = match key.code {
+        KeyCode::Enter => Some(AddMode::Play),
+        KeyCode::Tab => Some(AddMode::Append),
+        _ => None,
+    };
+    if let Some(mode) = mode {
+        let before = History::snapshot(client);
+        let selected_id = app.selected_id(client);
+        app.finder_mut().pick(client, mode);
+        if let Some(before) = before {
+            app.record_queue(client, before, selected_id);
+        }
+        return;
+    }
+
+    let finder = app.finder_mut();
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Esc => finder.close(),
+        KeyCode::Down => finder.next(),
+        KeyCode::Up => finder.previous(),
+        KeyCode::Char('n') if control => finder.next(),
+        KeyCode::Char('p') if control => finder.previous(),
+        _ => {
+            if finder.input_mut().handle_key(key) {
+                finder.find();
+            }
         }
     }
 }
 
+/// Clicks select tabs and songs of the queue, clicking a song twice plays it,
+/// the wheel scrolls the queue or changes the volume over it, clicking or dragging on the progress bar seeks
+fn mouse_event(app: &mut App, client: &mut Mpd, config: &Config, mouse: MouseEvent) {
+    let (x, y) = (mouse.column, mouse.row);
+    let contains = |area: Rect| area.intersects(Rect::new(x, y, 1, 1));
+    let areas = app.areas();
+    let progress = areas.progress;
+    let over_volume = contains(areas.volume);
+    let tab = areas.tabs.iter().position(|area| contains(*area));
+    let on_queue = app.tab_index() == QUEUE_TAB;
+
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left)
+            if contains(progress) =>
+        {
+            client.seek_ratio(f64::from(x - progress.x) / f64::from(progress.width));
+        }
+        MouseEventKind::Down(MouseButton::Left) => match tab {
+            Some(tab) => app.select_tab(client, tab),
+            None if on_queue => match app.state_mut().column_at(x, y) {
+                Some(column) => {
+                    let before = History::snapshot(client);
+                    let selected_id = app.selected_id(client);
+                    app.sort_by_column(client, config, column);
+                    if let Some(before) = before {
+                        app.record_queue(client, before, selected_id);
+                    }
+                }
+                None => app.click_queue(client, x, y),
+            },
+            None => (),
+        },
+        MouseEventKind::ScrollUp if over_volume => change_volume(client, 5),
+        MouseEventKind::ScrollDown if over_volume => change_volume(client, -5),
+        MouseEventKind::ScrollUp if on_queue => app.scroll(-SCROLL_LINES),
+        MouseEventKind::ScrollDown if on_queue => app.scroll(SCROLL_LINES),
+        _ => (),
+    }
+}
+
 fn change_volume(client: &mut Mpd, delta: i8) {
     let volume = client.status().volume;
     let changed = volume + delta;
     if (0..=100).contains(&changed) {
-        if let Err(e) = client.client_mut().volume(changed) {
-            println!("{:?}", e);
-        };
+        client.command(|client| client.volume(changed));
+    }
+}
+
+/// Sets the title of the terminal window from the playing song, if it changed
+fn set_window_title(last: &mut String, config: &Config, client: &Mpd) -> std::io::Result<()> {
+    let title = client
+        .curr_song()
+        .map(|song| {
+            config
+                .formats()
+                .window_title()
+                .text(song, Some(client.status()))
+        })
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| "empirust".to_string());
+    if title != *last {
+        execute!(std::io::stdout(), SetTitle(&title))?;
+        *last = title;
     }
+    Ok(())
 }
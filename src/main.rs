@@ -1,4 +1,8 @@
-use crate::{config::Config, ui::app::App};
+use crate::{
+    config::{Config, USAGE},
+    mpd::Mpd,
+    ui::app::App,
+};
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -7,21 +11,29 @@ use crossterm::{
 use std::error::Error;
 use tui::{backend::CrosstermBackend, Terminal};
 
+mod command;
 mod config;
+mod events;
+mod idle;
 mod input;
 mod mpd;
+mod template;
 mod ui;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // connect to mpd server and create an mpd data holder
-    let client = ::mpd::Client::connect("127.0.0.1:6600").unwrap();
-    let client = crate::mpd::Mpd::new(client).unwrap();
+    if std::env::args().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
 
     // parse config
-    let config = Config::new().unwrap();
-
-    // setup UI
-    let app = App::new(&client, &config);
+    let config = match Config::new() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Config Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     // setup terminal
     enable_raw_mode()?;
@@ -30,8 +42,22 @@ fn main() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    // connect to mpd server and create an mpd data holder
+    let client = Mpd::new(config.connection().clone());
+
     // handle input
-    if let Err(e) = input::input(&mut terminal, app, client, &config) {
+    let result = match client {
+        Ok(client) => {
+            // setup UI
+            let app = App::new(&client, &config);
+            input::input(&mut terminal, app, client, config)
+        }
+        Err(e) => {
+            let message = format!("Could not connect to MPD at {}: {}", config.connection(), e);
+            input::error_screen(&mut terminal, &message)
+        }
+    };
+    if let Err(e) = result {
         println!("Input Error: {:?}\r", e);
     }
 
@@ -1,15 +1,233 @@
 //! MPD data holder
 
-use mpd::{Client, Playlist, Song, Status};
-use std::error::Error;
+use crate::config::Connection;
+use mpd::{error::ServerError, Client, Id, Playlist, Query, Song, State, Status, Subsystem, Term};
+use std::{
+    cmp::Ordering,
+    error::Error,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+#[derive(Debug)]
+/// Connection to MPD either over TCP or a Unix socket
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => stream.try_clone().map(Self::Tcp),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.try_clone().map(Self::Unix),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Second handle on the socket of a client, for the commands the client has no method for.
+/// It is only used in between the commands of the client, so their replies do not mix
+struct RawStream(BufReader<Stream>);
+
+impl RawStream {
+    /// Sends a command and returns the `key: value` pairs of its reply
+    fn command(&mut self, command: &str) -> Result<Vec<(String, String)>, mpd::error::Error> {
+        let stream = self.0.get_mut();
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+
+        let mut pairs = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.0.read_line(&mut line)? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let line = line.trim_end_matches('\n');
+            if line == "OK" {
+                return Ok(pairs);
+            }
+            if line.starts_with("ACK ") {
+                return Err(line.parse::<ServerError>()?.into());
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                pairs.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+}
+
+/// Connects to MPD and authenticates if a password is set
+pub fn connect(connection: &Connection) -> Result<Client<Stream>, mpd::error::Error> {
+    connect_with_raw(connection).map(|(client, _)| client)
+}
+
+/// Connects like `connect`, also returning a raw handle on the same connection
+fn connect_with_raw(
+    connection: &Connection,
+) -> Result<(Client<Stream>, RawStream), mpd::error::Error> {
+    let stream = match connection.socket() {
+        #[cfg(unix)]
+        Some(socket) => Stream::Unix(UnixStream::connect(socket)?),
+        #[cfg(not(unix))]
+        Some(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            )
+            .into())
+        }
+        None => Stream::Tcp(connect_tcp(connection.host(), connection.port())?),
+    };
+
+    let raw = RawStream(BufReader::new(stream.try_clone()?));
+    let mut client = Client::new(stream)?;
+    if let Some(password) = connection.password() {
+        client.login(password)?;
+    }
+
+    Ok((client, raw))
+}
+
+/// Connects to the first address the host resolves to, not hanging on unreachable hosts
+fn connect_tcp(host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host could not be resolved");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Songs fetched at a time when listing the whole library
+const LIBRARY_PAGE_SIZE: u32 = 5000;
+/// Most commands sent in one command list, MPD refuses lists over a couple of megabytes
+const COMMAND_LIST_SIZE: usize = 1000;
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(16);
+/// MPD drops connections which were quiet for a while, so the main one gets pinged
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// For how long an error returned by MPD stays visible
+const ERROR_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+/// Entry of a directory in MPD's database
+pub enum Entry {
+    /// Path of the directory relative to the music directory
+    Directory(String),
+    Song(Song),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Where songs get added to the queue
+pub enum AddMode {
+    Append,
+    /// Right after the playing song
+    InsertNext,
+    /// In place of the whole queue, starting to play the first one
+    Replace,
+    /// At the end of the queue, starting to play the first one
+    Play,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// State of the single and consume modes
+pub enum ModeState {
+    #[default]
+    Off,
+    On,
+    /// On until the current song ends
+    Oneshot,
+}
+
+impl ModeState {
+    fn parse(value: &str) -> Self {
+        match value {
+            "1" => Self::On,
+            "oneshot" => Self::Oneshot,
+            _ => Self::Off,
+        }
+    }
+
+    /// Argument of the command setting the mode
+    const fn arg(self) -> &'static str {
+        match self {
+            Self::Off => "0",
+            Self::On => "1",
+            Self::Oneshot => "oneshot",
+        }
+    }
+
+    const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::On,
+            Self::On => Self::Oneshot,
+            Self::Oneshot => Self::Off,
+        }
+    }
+}
 
 #[derive(Debug)]
 /// Holds MPD's data
 pub struct Mpd {
-    client: Client,
+    connection: Connection,
+    /// Is `None` while disconnected
+    client: Option<Client<Stream>>,
+    /// Raw handle on the connection of `client`
+    raw: Option<RawStream>,
+    reconnect_at: Instant,
+    reconnect_delay: Duration,
+    error: Option<(String, Instant)>,
+    last_command: Instant,
     status: Status,
+    /// `status` tells oneshot apart from off for neither of these
+    single: ModeState,
+    consume: ModeState,
+    /// When the status was fetched, used to guess the elapsed time in between
+    status_at: Instant,
     playlists: Option<Vec<Playlist>>,
     queue: Option<Vec<Song>>,
+    /// Version of the queue held in `queue`
+    queue_version: u32,
     curr_song: Option<Song>,
     prev_playing_pos: u32,
     curr_playing_pos: u32,
@@ -17,31 +235,100 @@ pub struct Mpd {
 }
 
 impl Mpd {
-    pub(crate) fn new(mut client: Client) -> Result<Self, Box<dyn Error>> {
+    pub(crate) fn new(connection: Connection) -> Result<Self, Box<dyn Error>> {
+        let (client, raw) = connect_with_raw(&connection)?;
+        let mut mpd = Self {
+            connection,
+            client: Some(client),
+            raw: Some(raw),
+            reconnect_at: Instant::now(),
+            reconnect_delay: RECONNECT_MIN_DELAY,
+            error: None,
+            last_command: Instant::now(),
+            status: Status::default(),
+            single: ModeState::Off,
+            consume: ModeState::Off,
+            status_at: Instant::now(),
+            playlists: None,
+            queue: None,
+            queue_version: 0,
+            curr_song: None,
+            prev_playing_pos: 0,
+            curr_playing_pos: 0,
+            curr_song_duration: 1,
+        };
+        mpd.fetch_all()?;
+
+        Ok(mpd)
+    }
+
+    /// Fetches all the data from scratch
+    fn fetch_all(&mut self) -> Result<(), mpd::error::Error> {
+        let Some(client) = self.client.as_mut() else {
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+        };
+
         let status = client.status()?;
-        let playlists = client.playlists().ok();
-        let queue = client.queue().ok();
-        let curr_song = client.currentsong().map_or(None, |arg| arg);
-        let curr_playing_pos = status.song.map_or(0, |arg| arg.pos);
-        let curr_song_duration: u16 = status
+        self.playlists = client.playlists().ok();
+        self.queue = client.queue().ok();
+        self.queue_version = status.queue_version;
+        self.curr_song = client.currentsong()?;
+        self.curr_playing_pos = status.song.map_or(0, |arg| arg.pos);
+        self.prev_playing_pos = self.curr_playing_pos;
+        self.curr_song_duration = status
             .time
-            .map_or(1, |time| time.1.num_seconds().try_into().unwrap_or(1));
-
-        Ok(Self {
-            client,
-            status,
-            playlists,
-            queue,
-            curr_song,
-            prev_playing_pos: curr_playing_pos,
-            curr_playing_pos,
-            curr_song_duration,
-        })
-    }
-
-    pub(crate) fn update(&mut self) {
-        self.status = self.client.status().unwrap();
-        self.curr_song = self.client.currentsong().map_or(None, |arg| arg);
+            .map_or(1, |time| time.1.as_secs().try_into().unwrap_or(1));
+        self.status = status;
+        self.status_at = Instant::now();
+        self.update_modes();
+
+        Ok(())
+    }
+
+    /// Refreshes the data affected by the changed subsystems
+    pub(crate) fn update(&mut self, changed: &[Subsystem]) {
+        if self.client.is_none() {
+            self.reconnect();
+            return;
+        }
+
+        let status_changed = changed.iter().any(|subsystem| {
+            matches!(
+                subsystem,
+                Subsystem::Player
+                    | Subsystem::Queue
+                    | Subsystem::Mixer
+                    | Subsystem::Options
+                    | Subsystem::Output
+            )
+        });
+        if status_changed {
+            self.update_status();
+        }
+        if changed.contains(&Subsystem::Options) {
+            self.update_modes();
+        }
+        if changed.contains(&Subsystem::Player) {
+            if let Some(curr_song) = self.command(|client| client.currentsong()) {
+                self.curr_song = curr_song;
+            }
+        }
+        if changed.contains(&Subsystem::Queue) {
+            self.update_queue();
+        }
+        if changed.contains(&Subsystem::Playlist) {
+            if let Some(playlists) = self.command(|client| client.playlists()) {
+                self.playlists = Some(playlists);
+            }
+        }
+    }
+
+    fn update_status(&mut self) {
+        let Some(status) = self.command(|client| client.status()) else {
+            return;
+        };
+        self.status = status;
+        self.status_at = Instant::now();
         self.curr_playing_pos = self.status.song.map_or(0, |arg| arg.pos);
 
         // update data of the new song
@@ -50,18 +337,267 @@ impl Mpd {
             self.curr_song_duration = self
                 .status
                 .time
-                .map_or(0, |time| time.1.num_seconds().try_into().unwrap_or(0));
+                .map_or(0, |time| time.1.as_secs().try_into().unwrap_or(0));
+        }
+    }
+
+    /// Reads the single and consume modes from the raw status
+    fn update_modes(&mut self) {
+        let Some(status) = self.raw_command("status") else {
+            return;
+        };
+        for (key, value) in status {
+            match key.as_str() {
+                "single" => self.single = ModeState::parse(&value),
+                "consume" => self.consume = ModeState::parse(&value),
+                _ => (),
+            }
+        }
+    }
+
+    /// Applies the changes made to the queue since the version held, instead of fetching it whole
+    fn update_queue(&mut self) {
+        let version = self.queue_version;
+        if version == self.status.queue_version {
+            return;
+        }
+        let Some(changes) = self.command(|client| client.changes(version)) else {
+            return;
+        };
+
+        let queue = self.queue.get_or_insert_with(Vec::new);
+        if !apply_changes(queue, self.status.queue_len as usize, changes) {
+            // a song is missing in between, the queue is fetched whole instead
+            let Some(queue) = self.command(|client| client.queue()) else {
+                return;
+            };
+            self.queue = Some(queue);
+        }
+        self.queue_version = self.status.queue_version;
+    }
+
+    /// Keeps the connection alive or tries to reconnect
+    pub(crate) fn tick(&mut self) {
+        if self.client.is_none() {
+            self.reconnect();
+        } else if self.last_command.elapsed() >= KEEPALIVE_INTERVAL {
+            self.command(|client| client.ping());
+        }
+    }
+
+    /// Runs a command on the client.
+    /// Errors returned by MPD are kept to be shown, any other one is treated as a lost connection
+    pub(crate) fn command<T>(
+        &mut self,
+        f: impl FnOnce(&mut Client<Stream>) -> Result<T, mpd::error::Error>,
+    ) -> Option<T> {
+        let client = self.client.as_mut()?;
+        self.last_command = Instant::now();
+        let result = f(client);
+        self.catch(result)
+    }
+
+    /// Runs a command the client has no method for, returning the `key: value` pairs of its reply
+    fn raw_command(&mut self, command: &str) -> Option<Vec<(String, String)>> {
+        let raw = self.raw.as_mut()?;
+        self.last_command = Instant::now();
+        let result = raw.command(command);
+        self.catch(result)
+    }
+
+    /// Keeps an error returned by MPD to be shown, any other one is treated as a lost connection
+    fn catch<T>(&mut self, result: Result<T, mpd::error::Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(mpd::error::Error::Server(e)) => {
+                self.error = Some((e.detail, Instant::now()));
+                None
+            }
+            Err(_) => {
+                self.disconnect();
+                None
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.raw = None;
+        self.reconnect_delay = RECONNECT_MIN_DELAY;
+        self.reconnect_at = Instant::now() + self.reconnect_delay;
+    }
+
+    /// Tries to connect again once the backoff delay has passed
+    fn reconnect(&mut self) {
+        if Instant::now() < self.reconnect_at {
+            return;
+        }
+
+        let (client, raw) = connect_with_raw(&self.connection).ok().unzip();
+        self.client = client;
+        self.raw = raw;
+        if self.client.is_some() && self.fetch_all().is_ok() {
+            self.reconnect_delay = RECONNECT_MIN_DELAY;
+        } else {
+            self.client = None;
+            self.raw = None;
+            self.reconnect_delay = (self.reconnect_delay * 2).min(RECONNECT_MAX_DELAY);
+            self.reconnect_at = Instant::now() + self.reconnect_delay;
+        }
+    }
+
+    pub(crate) const fn connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub(crate) const fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Time left until the next reconnection attempt
+    pub(crate) fn reconnect_in(&self) -> Duration {
+        self.reconnect_at.saturating_duration_since(Instant::now())
+    }
+
+    /// Time left until `tick` has something to do
+    pub(crate) fn tick_in(&self) -> Duration {
+        if self.client.is_none() {
+            self.reconnect_in()
+        } else {
+            KEEPALIVE_INTERVAL.saturating_sub(self.last_command.elapsed())
         }
     }
 
-    pub(crate) fn client_mut(&mut self) -> &mut Client {
-        &mut self.client
+    /// Shows an error the way the ones returned by MPD are
+    pub(crate) fn report(&mut self, error: String) {
+        self.error = Some((error, Instant::now()));
+    }
+
+    /// The last error returned by MPD if it is recent enough
+    pub(crate) fn error(&self) -> Option<&str> {
+        self.error
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < ERROR_TIMEOUT)
+            .map(|(error, _)| error.as_str())
     }
 
     pub(crate) const fn status(&self) -> &Status {
         &self.status
     }
 
+    /// Elapsed and total time of the current song, counting the time passed since the status was fetched
+    pub(crate) fn elapsed(&self) -> Option<(Duration, Duration)> {
+        let (elapsed, total) = self.status.time?;
+        let mut elapsed = self.status.elapsed.unwrap_or(elapsed);
+        if self.status.state == State::Play {
+            elapsed += self.status_at.elapsed();
+        }
+
+        Some((elapsed.min(total), total))
+    }
+
+    /// Seeks in the current song, showing the new position right away instead of waiting for the status
+    pub(crate) fn seek_to(&mut self, position: Duration) {
+        let Some((_, total)) = self.status.time else {
+            return;
+        };
+        let position = position.min(total);
+        if self.command(|client| client.rewind(position)).is_some() {
+            self.status.elapsed = Some(position);
+            self.status_at = Instant::now();
+        }
+    }
+
+    /// Seeks by `delta` seconds relative to where MPD is, then shows the new position right away
+    pub(crate) fn seek_by(&mut self, delta: i64) {
+        if self.status.time.is_none() {
+            return;
+        }
+        if self.raw_command(&format!("seekcur {:+}", delta)).is_some() {
+            self.update_status();
+        }
+    }
+
+    /// Seeks to the fraction of the current song, `ratio` being from 0 to 1
+    pub(crate) fn seek_ratio(&mut self, ratio: f64) {
+        let Some((_, total)) = self.status.time else {
+            return;
+        };
+        self.seek_to(total.mul_f64(ratio.clamp(0.0, 1.0)));
+    }
+
+    pub(crate) fn set_repeat(&mut self, repeat: bool) {
+        if self.command(|client| client.repeat(repeat)).is_some() {
+            self.status.repeat = repeat;
+        }
+    }
+
+    pub(crate) fn set_random(&mut self, random: bool) {
+        if self.command(|client| client.random(random)).is_some() {
+            self.status.random = random;
+        }
+    }
+
+    pub(crate) fn toggle_repeat(&mut self) {
+        self.set_repeat(!self.status.repeat);
+    }
+
+    pub(crate) fn toggle_random(&mut self) {
+        self.set_random(!self.status.random);
+    }
+
+    pub(crate) fn set_single(&mut self, single: ModeState) {
+        if self
+            .raw_command(&format!("single {}", single.arg()))
+            .is_some()
+        {
+            self.single = single;
+        }
+    }
+
+    pub(crate) fn set_consume(&mut self, consume: ModeState) {
+        if self
+            .raw_command(&format!("consume {}", consume.arg()))
+            .is_some()
+        {
+            self.consume = consume;
+        }
+    }
+
+    /// Switches the single mode to the next of off, on and oneshot
+    pub(crate) fn cycle_single(&mut self) {
+        self.set_single(self.single.next());
+    }
+
+    /// Switches the consume mode to the next of off, on and oneshot
+    pub(crate) fn cycle_consume(&mut self) {
+        self.set_consume(self.consume.next());
+    }
+
+    pub(crate) fn set_crossfade(&mut self, seconds: u32) {
+        let crossfade = Duration::from_secs(seconds.into());
+        if self.command(|client| client.crossfade(crossfade)).is_some() {
+            self.status.crossfade = Some(crossfade).filter(|crossfade| !crossfade.is_zero());
+        }
+    }
+
+    pub(crate) const fn single(&self) -> ModeState {
+        self.single
+    }
+
+    pub(crate) const fn consume(&self) -> ModeState {
+        self.consume
+    }
+
+    /// Stops playing, showing it right away
+    pub(crate) fn stop(&mut self) {
+        if self.command(|client| client.stop()).is_some() {
+            self.status.state = State::Stop;
+            self.status.elapsed = None;
+            self.status.time = None;
+        }
+    }
+
     pub(crate) const fn playlists(&self) -> Option<&Vec<Playlist>> {
         self.playlists.as_ref()
     }
@@ -70,11 +606,419 @@ impl Mpd {
         self.queue.as_ref()
     }
 
+    pub(crate) const fn queue_version(&self) -> u32 {
+        self.queue_version
+    }
+
+    /// Position of the song in the queue
+    pub(crate) fn queue_pos(&self, id: Id) -> Option<usize> {
+        self.queue()?
+            .iter()
+            .position(|song| song.place.is_some_and(|place| place.id == id))
+    }
+
     pub(crate) const fn curr_song(&self) -> Option<&Song> {
         self.curr_song.as_ref()
     }
 
-    pub(crate) const fn curr_playing_pos(&self) -> u32 {
-        self.curr_playing_pos
+    /// Lists the subdirectories and songs of the directory, the root one being `""`
+    pub(crate) fn list_directory(&mut self, path: &str) -> Option<Vec<Entry>> {
+        let dir = Song {
+            file: path.to_string(),
+            ..Song::default()
+        };
+        let (files, songs) =
+            self.command(|client| Ok((client.listfiles(path)?, client.lsinfo(&dir)?)))?;
+
+        // lsinfo's directories get lost when parsed into songs, so they are taken from listfiles
+        let directories =
+            files
+                .into_iter()
+                .filter(|(key, _)| key == "directory")
+                .map(|(_, name)| match path {
+                    "" => Entry::Directory(name),
+                    path => Entry::Directory(format!("{}/{}", path, name)),
+                });
+        let songs = songs
+            .into_iter()
+            .filter(|song| !song.file.is_empty())
+            .map(Entry::Song);
+
+        Some(directories.chain(songs).collect())
+    }
+
+    /// All the songs inside the directory and its subdirectories
+    pub(crate) fn songs_in_directory(&mut self, path: &str) -> Option<Vec<Song>> {
+        if path.is_empty() {
+            return self.command(|client| client.listall());
+        }
+
+        let mut query = Query::new();
+        query.and(Term::Base, path);
+        self.command(|client| client.find(&query, None))
+    }
+
+    /// Values of the tag among the songs matching all the `(tag, value)` filters
+    pub(crate) fn list_tag(&mut self, tag: &str, filters: &[(&str, &str)]) -> Option<Vec<String>> {
+        let query = Self::query(filters);
+        let values = self.command(|client| client.list(&Term::Tag(tag.into()), &query))?;
+
+        Some(
+            values
+                .into_iter()
+                .filter(|value| !value.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Songs matching all the `(tag, value)` filters exactly
+    pub(crate) fn find_songs(&mut self, filters: &[(&str, &str)]) -> Option<Vec<Song>> {
+        let query = Self::query(filters);
+        self.command(|client| client.find(&query, None))
+    }
+
+    /// Songs with the tag matching the value exactly, or containing it ignoring the case
+    pub(crate) fn search_songs(
+        &mut self,
+        term: Term,
+        value: &str,
+        exact: bool,
+    ) -> Option<Vec<Song>> {
+        let mut query = Query::new();
+        query.and(term, value);
+        if exact {
+            self.command(|client| client.find(&query, None))
+        } else {
+            self.command(|client| client.search(&query, None))
+        }
+    }
+
+    fn query<'a>(filters: &[(&'a str, &'a str)]) -> Query<'a> {
+        let mut query = Query::new();
+        for (tag, value) in filters {
+            query.and(Term::Tag((*tag).into()), *value);
+        }
+        query
+    }
+
+    /// Puts the queue in the order, which holds the current positions of the songs.
+    /// The songs out of place are moved in a single command list
+    pub(crate) fn reorder_queue(&mut self, order: &[usize]) {
+        let moves = self.reorder_commands(order);
+        if !moves.is_empty() {
+            self.command_list(&moves);
+        }
+    }
+
+    /// The `moveid` commands of `reorder_queue`, to send along with others
+    pub(crate) fn reorder_commands(&self, order: &[usize]) -> Vec<String> {
+        let Some(queue) = self.queue.as_ref() else {
+            return Vec::new();
+        };
+        moves_into_order(order)
+            .into_iter()
+            .filter_map(|(pos, to)| Some(format!("moveid {} {}", queue.get(pos)?.place?.id.0, to)))
+            .collect()
+    }
+
+    /// Runs the commands at once, returning the `key: value` pairs of all their replies.
+    /// MPD stops at the first one failing
+    pub(crate) fn command_list(&mut self, commands: &[String]) -> Option<Vec<(String, String)>> {
+        let command = format!(
+            "command_list_begin\n{}\ncommand_list_end",
+            commands.join("\n")
+        );
+        self.raw_command(&command)
+    }
+
+    /// Adds the songs to the queue, sending them in batches instead of one round trip each
+    pub(crate) fn add_songs(&mut self, songs: &[Song], mode: AddMode) {
+        let next_pos = self.status.song.map(|place| place.pos as usize + 1);
+        let adds = songs
+            .iter()
+            .enumerate()
+            .map(|(i, song)| match (mode, next_pos) {
+                (AddMode::InsertNext, Some(pos)) => {
+                    format!("addid {} {}", quote(&song.file), pos + i)
+                }
+                _ => format!("addid {}", quote(&song.file)),
+            });
+        let commands: Vec<String> = (mode == AddMode::Replace)
+            .then(|| "clear".to_string())
+            .into_iter()
+            .chain(adds)
+            .collect();
+
+        let mut first = None;
+        for chunk in commands.chunks(COMMAND_LIST_SIZE) {
+            let Some(reply) = self.command_list(chunk) else {
+                return;
+            };
+            first = first.or_else(|| {
+                reply
+                    .into_iter()
+                    .find(|(key, _)| key == "Id")
+                    .map(|(_, id)| id)
+            });
+        }
+        match (mode, first) {
+            (AddMode::Play, Some(id)) => self.raw_command(&format!("playid {}", id)),
+            (AddMode::Replace, Some(_)) => self.raw_command("play 0"),
+            _ => None,
+        };
+    }
+}
+
+/// Patches the queue with the songs `plchanges` returned, `len` being its new length.
+/// Returns `false` if a changed song would leave a gap, the queue is not to be trusted then
+fn apply_changes(queue: &mut Vec<Song>, len: usize, changes: Vec<Song>) -> bool {
+    queue.truncate(len);
+    for song in changes {
+        let Some(pos) = song.place.map(|place| place.pos as usize) else {
+            continue;
+        };
+        match pos.cmp(&queue.len()) {
+            Ordering::Less => queue[pos] = song,
+            Ordering::Equal => queue.push(song),
+            Ordering::Greater => return false,
+        }
+    }
+    queue.len() == len
+}
+
+/// Moves putting the songs in the order, where `order` holds their current positions.
+/// Each song in turn is moved to its final position, right after the ones moved before it;
+/// the ones still to be moved keep their order, so a song is in place if none of them
+/// is in front of it
+fn moves_into_order(order: &[usize]) -> Vec<(usize, usize)> {
+    // Fenwick tree counting the songs still to be moved by their current position
+    let len = order.len();
+    let mut tree = vec![0usize; len + 1];
+    for i in 1..=len {
+        tree[i] += 1;
+        let parent = i + (i & i.wrapping_neg());
+        if parent <= len {
+            tree[parent] += tree[i];
+        }
+    }
+
+    let mut moves = Vec::new();
+    for (to, &pos) in order.iter().enumerate() {
+        let mut in_front = 0;
+        let mut i = pos;
+        while i > 0 {
+            in_front += tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        if in_front > 0 {
+            moves.push((pos, to));
+        }
+        let mut i = pos + 1;
+        while i <= len {
+            tree[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+    moves
+}
+
+/// Every song in the database along with its tags.
+/// `listallinfo` is not exposed by the client, but every path contains the empty string.
+/// The songs are fetched a page at a time, MPD drops clients whose reply outgrows its output buffer
+pub fn list_all_info(client: &mut Client<Stream>) -> Result<Vec<Song>, mpd::error::Error> {
+    let mut query = Query::new();
+    query.and(Term::File, "");
+    let mut songs = Vec::new();
+    loop {
+        let start = songs.len() as u32;
+        let page = client.search(&query, (start, start + LIBRARY_PAGE_SIZE))?;
+        let last = page.len() < LIBRARY_PAGE_SIZE as usize;
+        songs.extend(page);
+        if last {
+            return Ok(songs);
+        }
+    }
+}
+
+/// Quotes an argument of a raw command
+pub fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Looks up the value of a tag which is not exposed as a dedicated field of `Song`
+pub fn song_tag<'a>(song: &'a Song, tag: &str) -> Option<&'a str> {
+    song.tags
+        .iter()
+        .find(|(key, _)| key == tag)
+        .map(|(_, value)| value.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpd::song::QueuePlace;
+    use std::{net::TcpListener, thread};
+
+    /// Acts as an MPD with nothing in it for one connection, hanging up once `drop_on` is sent
+    fn serve(stream: TcpStream, drop_on: Option<&str>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        writer.write_all(b"OK MPD 0.23.0\n").unwrap();
+        let mut in_list = false;
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let command = line.trim_end();
+            if drop_on == Some(command) {
+                return;
+            }
+            let reply = match command {
+                "status" => "volume: 50\nplaylist: 1\nplaylistlength: 0\nstate: stop\n",
+                _ => "",
+            };
+            in_list = match command {
+                "command_list_begin" | "command_list_ok_begin" => true,
+                "command_list_end" => false,
+                _ => in_list,
+            };
+            let ok = if in_list { "" } else { "OK\n" };
+            if writer
+                .write_all(format!("{}{}", reply, ok).as_bytes())
+                .is_err()
+            {
+                return;
+            }
+            line.clear();
+        }
+    }
+
+    fn song(pos: u32, file: &str) -> Song {
+        Song {
+            file: file.to_string(),
+            place: Some(QueuePlace {
+                id: Id(pos + 1),
+                pos,
+                prio: 0,
+            }),
+            ..Song::default()
+        }
+    }
+
+    fn files(queue: &[Song]) -> Vec<&str> {
+        queue.iter().map(|song| song.file.as_str()).collect()
+    }
+
+    #[test]
+    fn changes_truncate_the_queue() {
+        let mut queue = vec![song(0, "a"), song(1, "b"), song(2, "c")];
+        assert!(apply_changes(&mut queue, 1, Vec::new()));
+        assert_eq!(files(&queue), ["a"]);
+    }
+
+    #[test]
+    fn changes_replace_and_append_songs() {
+        let mut queue = vec![song(0, "a"), song(1, "b"), song(2, "c")];
+        let changes = vec![song(1, "x"), song(2, "y"), song(3, "z")];
+        assert!(apply_changes(&mut queue, 4, changes));
+        assert_eq!(files(&queue), ["a", "x", "y", "z"]);
+    }
+
+    #[test]
+    fn changes_leaving_a_gap_are_refused() {
+        let mut queue = vec![song(0, "a")];
+        assert!(!apply_changes(&mut queue, 3, vec![song(2, "c")]));
+
+        // songs past the end were not sent at all
+        let mut queue = vec![song(0, "a")];
+        assert!(!apply_changes(&mut queue, 2, Vec::new()));
+    }
+
+    /// Queue of the original positions after moving them as `moveid` would
+    fn apply_moves(len: usize, moves: &[(usize, usize)]) -> Vec<usize> {
+        let mut queue: Vec<usize> = (0..len).collect();
+        for &(pos, to) in moves {
+            let from = queue.iter().position(|&id| id == pos).unwrap();
+            let id = queue.remove(from);
+            queue.insert(to, id);
+        }
+        queue
+    }
+
+    #[test]
+    fn moves_put_the_songs_in_order() {
+        let orders: [&[usize]; 5] = [
+            &[],
+            &[0, 1, 2],
+            &[2, 1, 0],
+            &[3, 0, 4, 1, 2],
+            &[1, 5, 0, 2, 6, 3, 4],
+        ];
+        for order in orders {
+            let moves = moves_into_order(order);
+            assert_eq!(apply_moves(order.len(), &moves), order);
+        }
+    }
+
+    #[test]
+    fn songs_already_in_place_are_not_moved() {
+        assert!(moves_into_order(&[0, 1, 2, 3]).is_empty());
+        // only the song jumping ahead needs a move
+        assert_eq!(moves_into_order(&[3, 0, 1, 2]), [(3, 0)]);
+        assert_eq!(moves_into_order(&[1, 2, 3, 0]).len(), 3);
+    }
+
+    #[test]
+    fn reconnects_after_the_server_drops_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (first, _) = listener.accept().unwrap();
+            serve(first, Some("ping"));
+            let (second, _) = listener.accept().unwrap();
+            serve(second, None);
+        });
+
+        let connection: Connection = toml::from_str(&format!("port = {}", port)).unwrap();
+        let mut mpd = Mpd::new(connection).unwrap();
+        assert!(mpd.connected());
+
+        // the server hangs up instead of answering
+        assert!(mpd.command(|client| client.ping()).is_none());
+        assert!(!mpd.connected());
+
+        // nothing is tried before the backoff delay has passed
+        mpd.tick();
+        assert!(!mpd.connected());
+
+        mpd.reconnect_at = Instant::now();
+        mpd.tick();
+        assert!(mpd.connected());
+        assert_eq!(mpd.reconnect_delay, RECONNECT_MIN_DELAY);
+        assert!(mpd.command(|client| client.ping()).is_some());
+
+        drop(mpd);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn backs_off_while_the_server_is_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (first, _) = listener.accept().unwrap();
+            serve(first, Some("ping"));
+        });
+
+        let connection: Connection = toml::from_str(&format!("port = {}", port)).unwrap();
+        let mut mpd = Mpd::new(connection).unwrap();
+        mpd.command(|client| client.ping());
+        server.join().unwrap();
+
+        // the listener is gone with the thread, so every attempt fails and doubles the delay
+        for delay in [1, 2, 4] {
+            mpd.reconnect_at = Instant::now();
+            mpd.tick();
+            assert!(!mpd.connected());
+            assert_eq!(mpd.reconnect_delay, RECONNECT_MIN_DELAY * delay * 2);
+        }
     }
 }
@@ -0,0 +1,476 @@
+//! Templates the songs are shown with, like `{%a - }{%t}|{%f}`
+//!
+//! * `%t`, `%20t`: a field, cut to 20 chars. `%(genre)` names any field of the queue columns
+//! * `{...}`: a section, dropped if a field directly inside it is empty
+//! * `{...}|{...}`: the first section which is not dropped
+//! * `$(red bold)`, `$(bg=black)`, `$(/)`: the style of the text after it, until the section ends
+//! * `\{`: a char taken as it is
+
+use crate::{
+    config::{parse_color, Field},
+    ui::queue::format_field,
+};
+use mpd::{Song, Status};
+use serde::{
+    de::{self, value::StrDeserializer},
+    Deserialize, Deserializer,
+};
+use std::{fmt, iter::Peekable, str::CharIndices, str::FromStr};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Text(String),
+    Field {
+        field: Field,
+        /// Chars the value is cut to
+        width: Option<usize>,
+    },
+    Style(Style),
+    /// Back to the style the section started with
+    Reset,
+    /// Alternatives, the first one with all its fields shown is
+    Section(Vec<Vec<Item>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Char of the template the error is at, from 0
+    position: usize,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at char {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Template {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            source,
+            chars: source.char_indices().peekable(),
+        };
+        let items = parser.items(None)?;
+        Ok(Self { items })
+    }
+}
+
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        source.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Template {
+    /// Spans of the song, `status` is of the song playing if it is the one
+    pub(crate) fn render(&self, song: &Song, status: Option<&Status>) -> Spans<'static> {
+        let mut spans: Vec<(String, Style)> = Vec::new();
+        render_items(&self.items, song, status, Style::default(), &mut spans);
+        Spans::from(
+            spans
+                .into_iter()
+                .map(|(text, style)| Span::styled(text, style))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Text of the song without the styles
+    pub(crate) fn text(&self, song: &Song, status: Option<&Status>) -> String {
+        let mut spans = Vec::new();
+        render_items(&self.items, song, status, Style::default(), &mut spans);
+        spans.into_iter().map(|(text, _)| text).collect()
+    }
+}
+
+/// Pushes the items to `spans`, returns false if any of the fields was empty
+fn render_items(
+    items: &[Item],
+    song: &Song,
+    status: Option<&Status>,
+    base: Style,
+    spans: &mut Vec<(String, Style)>,
+) -> bool {
+    let mut style = base;
+    let mut complete = true;
+    let mut push = |text: String, style: Style| match spans.last_mut() {
+        Some((last, last_style)) if *last_style == style => last.push_str(&text),
+        _ if text.is_empty() => (),
+        _ => spans.push((text, style)),
+    };
+
+    for item in items {
+        match item {
+            Item::Text(text) => push(text.clone(), style),
+            Item::Field { field, width } => {
+                let value = format_field(song, *field, status).unwrap_or_default();
+                complete &= !value.is_empty();
+                push(truncate(value, *width), style);
+            }
+            Item::Style(patch) => style = style.patch(*patch),
+            Item::Reset => style = base,
+            Item::Section(alternatives) => {
+                for alternative in alternatives {
+                    let mut section = Vec::new();
+                    if render_items(alternative, song, status, style, &mut section) {
+                        section
+                            .into_iter()
+                            .for_each(|(text, style)| push(text, style));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    complete
+}
+
+/// Cuts the value to `width` chars, ending it with `…` if it is longer
+fn truncate(value: String, width: Option<usize>) -> String {
+    match width {
+        Some(width) if value.chars().count() > width => value
+            .chars()
+            .take(width - 1)
+            .chain(std::iter::once('…'))
+            .collect(),
+        _ => value,
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Items up to the end of the section opened at `open`, or of the template if it is `None`
+    fn items(&mut self, open: Option<usize>) -> Result<Vec<Item>, ParseError> {
+        let mut items = Vec::new();
+        let mut text = String::new();
+        loop {
+            let Some(&(at, c)) = self.chars.peek() else {
+                return match open {
+                    Some(open) => Err(self.error(open, "unclosed `{`")),
+                    None => {
+                        push_text(&mut items, &mut text);
+                        Ok(items)
+                    }
+                };
+            };
+            match c {
+                '}' => {
+                    self.chars.next();
+                    if open.is_none() {
+                        return Err(self.error(at, "`}` without a `{` before it"));
+                    }
+                    push_text(&mut items, &mut text);
+                    return Ok(items);
+                }
+                '{' => {
+                    push_text(&mut items, &mut text);
+                    items.push(self.section()?);
+                }
+                '|' => {
+                    return Err(self.error(at, "`|` can only be between sections, like `{%t}|{%f}`"))
+                }
+                '%' => {
+                    push_text(&mut items, &mut text);
+                    items.push(self.field()?);
+                }
+                '$' => {
+                    push_text(&mut items, &mut text);
+                    items.push(self.style()?);
+                }
+                '\\' => {
+                    self.chars.next();
+                    let (_, c) = self
+                        .chars
+                        .next()
+                        .ok_or_else(|| self.error(at, "nothing to escape after `\\`"))?;
+                    text.push(c);
+                }
+                _ => {
+                    self.chars.next();
+                    text.push(c);
+                }
+            }
+        }
+    }
+
+    /// `{...}`, along with the alternatives after it
+    fn section(&mut self) -> Result<Item, ParseError> {
+        let mut alternatives = Vec::new();
+        loop {
+            let (open, _) = self.chars.next().expect("section starts with `{`");
+            alternatives.push(self.items(Some(open))?);
+            match self.chars.peek() {
+                Some(&(bar, '|')) => {
+                    self.chars.next();
+                    if !matches!(self.chars.peek(), Some((_, '{'))) {
+                        return Err(self.error(bar, "expected a section after `|`"));
+                    }
+                }
+                _ => return Ok(Item::Section(alternatives)),
+            }
+        }
+    }
+
+    /// `%t`, `%20t`, `%(name)` or `%20(name)`
+    fn field(&mut self) -> Result<Item, ParseError> {
+        let (start, _) = self.chars.next().expect("field starts with `%`");
+        let mut digits = String::new();
+        while let Some(&(_, c)) = self.chars.peek().filter(|(_, c)| c.is_ascii_digit()) {
+            digits.push(c);
+            self.chars.next();
+        }
+        let width = match digits.parse::<usize>() {
+            Ok(0) => return Err(self.error(start, "a field cannot be cut to 0 chars")),
+            Ok(width) => Some(width),
+            Err(_) if digits.is_empty() => None,
+            Err(_) => return Err(self.error(start, "the width is too large")),
+        };
+
+        let field = match self.chars.next() {
+            None => return Err(self.error(start, "expected a field after `%`")),
+            Some((_, '(')) => {
+                let name = self.until(')', start, "unclosed `%(`")?;
+                Field::deserialize(StrDeserializer::<de::value::Error>::new(name))
+                    .map_err(|e| self.error(start, &e.to_string()))?
+            }
+            Some((at, c)) => field_by_letter(c)
+                .ok_or_else(|| self.error(at, &format!("unknown field `%{}`", c)))?,
+        };
+        Ok(Item::Field { field, width })
+    }
+
+    /// `$(red bold)` or `$(/)`
+    fn style(&mut self) -> Result<Item, ParseError> {
+        let (start, _) = self.chars.next().expect("style starts with `$`");
+        if !matches!(self.chars.next(), Some((_, '('))) {
+            return Err(self.error(start, "expected `(` after `$`, escape it as `\\$`"));
+        }
+        let words = self.until(')', start, "unclosed `$(`")?;
+        if words.trim() == "/" {
+            return Ok(Item::Reset);
+        }
+
+        let mut style = Style::default();
+        for word in words.split_whitespace() {
+            style = if let Some(color) = word.strip_prefix("bg=") {
+                style.bg(parse_color(color).map_err(|e| self.error(start, &e))?)
+            } else if let Some(modifier) = modifier_by_name(word) {
+                style.add_modifier(modifier)
+            } else {
+                let color = word.strip_prefix("fg=").unwrap_or(word);
+                let color = parse_color(color)
+                    .map_err(|_| self.error(start, &format!("unknown style `{}`", word)))?;
+                style.fg(color)
+            };
+        }
+        Ok(Item::Style(style))
+    }
+
+    /// Text up to the `end` char, which is skipped
+    fn until(&mut self, end: char, start: usize, unclosed: &str) -> Result<&'a str, ParseError> {
+        let from = self.chars.peek().map_or(self.source.len(), |(i, _)| *i);
+        for (i, c) in self.chars.by_ref() {
+            if c == end {
+                return Ok(&self.source[from..i]);
+            }
+        }
+        Err(self.error(start, unclosed))
+    }
+
+    /// Error at the byte `at` of the source, counted in chars
+    fn error(&self, at: usize, message: &str) -> ParseError {
+        ParseError {
+            position: self.source[..at].chars().count(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn push_text(items: &mut Vec<Item>, text: &mut String) {
+    if !text.is_empty() {
+        items.push(Item::Text(std::mem::take(text)));
+    }
+}
+
+/// Fields which have a letter, mostly as ncmpcpp names them
+const fn field_by_letter(letter: char) -> Option<Field> {
+    let field = match letter {
+        'a' => Field::Tag("Artist"),
+        'A' => Field::Tag("AlbumArtist"),
+        't' => Field::Tag("Title"),
+        'b' => Field::Tag("Album"),
+        'y' => Field::Tag("Date"),
+        'n' => Field::Tag("Track"),
+        'g' => Field::Tag("Genre"),
+        'c' => Field::Tag("Composer"),
+        'p' => Field::Tag("Performer"),
+        'd' => Field::Tag("Disc"),
+        'C' => Field::Tag("Comment"),
+        'f' => Field::Filename,
+        'F' => Field::File,
+        'e' => Field::Extension,
+        'l' => Field::Duration,
+        'P' => Field::Priority,
+        _ => return None,
+    };
+    Some(field)
+}
+
+fn modifier_by_name(name: &str) -> Option<Modifier> {
+    let modifier = match name {
+        "bold" => Modifier::BOLD,
+        "dim" => Modifier::DIM,
+        "italic" => Modifier::ITALIC,
+        "underlined" => Modifier::UNDERLINED,
+        "reversed" => Modifier::REVERSED,
+        "crossed_out" => Modifier::CROSSED_OUT,
+        _ => return None,
+    };
+    Some(modifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tui::style::Color;
+
+    fn song() -> Song {
+        Song {
+            file: "music/artist/album/01 song.flac".to_string(),
+            title: Some("Song".to_string()),
+            artist: Some("Artist".to_string()),
+            duration: Some(Duration::from_secs(125)),
+            tags: vec![("Album".to_string(), "Album".to_string())],
+            ..Song::default()
+        }
+    }
+
+    fn text(template: &str, song: &Song) -> String {
+        template.parse::<Template>().unwrap().text(song, None)
+    }
+
+    fn error(template: &str) -> String {
+        template.parse::<Template>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn fields_and_text() {
+        assert_eq!(text("%a - %t (%l)", &song()), "Artist - Song (2:05)");
+        assert_eq!(text("%(album) / %e", &song()), "Album / flac");
+        assert_eq!(text("no fields", &song()), "no fields");
+        assert_eq!(text("", &song()), "");
+    }
+
+    #[test]
+    fn empty_field_outside_sections_is_shown_empty() {
+        assert_eq!(text("[%g]", &song()), "[]");
+    }
+
+    #[test]
+    fn sections_drop_when_a_field_is_empty() {
+        assert_eq!(text("{%a - }{%t}|{%f}", &song()), "Artist - Song");
+        assert_eq!(text("{%g - }%t", &song()), "Song");
+
+        let untagged = Song {
+            file: "dir/track.mp3".to_string(),
+            ..Song::default()
+        };
+        assert_eq!(text("{%a - }{%t}|{%f}", &untagged), "track.mp3");
+        assert_eq!(text("{%a}|{%g}", &untagged), "");
+    }
+
+    #[test]
+    fn nested_sections_do_not_drop_the_outer_one() {
+        assert_eq!(text("{%t{ (%g)}}", &song()), "Song");
+        assert_eq!(text("{%t{ (%b)}}", &song()), "Song (Album)");
+        assert_eq!(text("{%g}|{%y}|{%b}", &song()), "Album");
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(text("%3a", &song()), "Ar…");
+        assert_eq!(text("%6a", &song()), "Artist");
+        assert_eq!(text("%1(album)", &song()), "…");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            text(r"\{%t\} \| 100\% \$ \\", &song()),
+            r"{Song} | 100% $ \"
+        );
+    }
+
+    #[test]
+    fn styles() {
+        let template: Template = "$(red bold)%a$(/) - {$(bg=blue)%t$(italic)!}."
+            .parse()
+            .unwrap();
+        let spans = template.render(&song(), None);
+        let red = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+        let blue = Style::default().bg(Color::Blue);
+        let expected = vec![
+            Span::styled("Artist", red),
+            Span::raw(" - "),
+            Span::styled("Song", blue),
+            Span::styled("!", blue.add_modifier(Modifier::ITALIC)),
+            Span::raw("."),
+        ];
+        assert_eq!(spans.0, expected);
+    }
+
+    #[test]
+    fn styles_end_with_their_section() {
+        let template: Template = "{$(red)%a}%t".parse().unwrap();
+        let spans = template.render(&song(), None);
+        assert_eq!(
+            spans.0,
+            vec![
+                Span::styled("Artist", Style::default().fg(Color::Red)),
+                Span::raw("Song"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("{%a - %t"), "unclosed `{` at char 1");
+        assert_eq!(error("%t}"), "`}` without a `{` before it at char 3");
+        assert_eq!(
+            error("%a|%t"),
+            "`|` can only be between sections, like `{%t}|{%f}` at char 3"
+        );
+        assert_eq!(error("{%a}|%t"), "expected a section after `|` at char 5");
+        assert_eq!(error("%a - %q"), "unknown field `%q` at char 7");
+        assert_eq!(error("%"), "expected a field after `%` at char 1");
+        assert_eq!(error("%0t"), "a field cannot be cut to 0 chars at char 1");
+        assert_eq!(error("%(genre"), "unclosed `%(` at char 1");
+        assert_eq!(error("a %(gnere)"), "unknown field `gnere` at char 3");
+        assert_eq!(
+            error("$red"),
+            "expected `(` after `$`, escape it as `\\$` at char 1"
+        );
+        assert_eq!(error("$(bold"), "unclosed `$(` at char 1");
+        assert_eq!(error("é$(blod)"), "unknown style `blod` at char 2");
+        assert_eq!(error("%t\\"), "nothing to escape after `\\` at char 3");
+    }
+}
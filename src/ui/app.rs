@@ -1,13 +1,41 @@
 use crate::{
-    config::{self, Config},
-    mpd::Mpd,
-};
-use std::time::Duration;
-use tui::{
-    layout::Constraint,
-    style::{Color, Style},
-    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    config::{Column, Config},
+    mpd::{Mpd, Stream},
+    ui::{
+        browser::Browser,
+        command_line::CommandLine,
+        filter::QueueFilter,
+        finder::Finder,
+        history::{History, Snapshot},
+        library::Library,
+        playlists::StoredPlaylists,
+        prompt::{Prompt, PromptAction},
+        queue::{sort_order, QueueState, SortKey},
+        search::{Search, SearchScope},
+        text_input::TextInput,
+    },
 };
+use mpd::{Client, Id, Song, Subsystem};
+use std::time::{Duration, Instant};
+use tui::{layout::Rect, widgets::ListState};
+
+pub const QUEUE_TAB: usize = 0;
+pub const BROWSE_TAB: usize = 1;
+pub const LIBRARY_TAB: usize = 2;
+pub const PLAYLISTS_TAB: usize = 3;
+pub const SEARCH_TAB: usize = 4;
+
+/// Longest time between two clicks on a song for them to play it
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+#[derive(Debug, Default)]
+/// Where parts of the UI were rendered last, for the mouse
+pub struct Areas {
+    /// Title of every tab
+    pub(crate) tabs: Vec<Rect>,
+    pub(crate) volume: Rect,
+    pub(crate) progress: Rect,
+}
 
 #[derive(Debug)]
 /// Holds data of the application's ui
@@ -16,71 +44,629 @@ pub struct App<'app> {
     tick_rate: Duration,
     tab_titles: Vec<&'app str>,
     tab_index: usize,
-    state: TableState,
+    state: QueueState,
+    /// Pattern searched for in the queue
+    filter: QueueFilter,
+    /// Popup for choosing a playlist to add songs to
+    playlist_chooser: Option<ListState>,
+    browser: Browser,
+    library: Library,
+    playlists: StoredPlaylists,
+    search: Search,
+    finder: Finder,
+    /// Prompt taking over the keys until it is answered
+    prompt: Option<Prompt>,
+    command_line: CommandLine,
     max_items: usize,
-    header: Row<'app>,
-    // TODO: extend the logic of calculating of columns's widths
-    widths: Vec<Constraint>,
-    // table: Table<'app>,
+    /// Version of the queue the state was set for
+    queue_version: u32,
+    areas: Areas,
+    /// When and which song of the queue was clicked last
+    last_click: Option<(Instant, usize)>,
+    /// Column the queue was sorted by last by clicking it, and whether it was descending
+    last_sort: Option<(usize, bool)>,
+    /// Whether the queue shows album headers
+    grouped: bool,
+    /// Changes made to the queue, to undo them
+    history: History,
 }
 
 impl<'app> App<'app> {
     pub(crate) fn new(mpd: &Mpd, config: &Config) -> App<'app> {
         // setup state
         let pos: usize = mpd.status().song.map_or(0, |arg| arg.pos as usize);
-        let mut state = TableState::default();
+        let mut state = QueueState::default();
         state.select(Some(pos));
 
-        // setup header
-        let header_cells: Vec<Cell> = config
-            .playlist_layout()
-            .iter()
-            .map(|layout| {
-                let cell = match layout.0 {
-                    config::PlaylistLayout::File => "File",
-                    config::PlaylistLayout::Title => "Title",
-                    config::PlaylistLayout::Duration => "Duration",
-                    config::PlaylistLayout::Album => "Album",
-                    config::PlaylistLayout::Artist => "Artist",
-                    config::PlaylistLayout::Track => "Track",
-                };
-                Cell::from(cell).style(Style::default().fg(Color::Cyan))
-            })
-            .collect();
-        let header = Row::new(header_cells)
-            .style(config.styles().normal())
-            .height(1)
-            .bottom_margin(1);
-
-        // setup widths
-        let widths: Vec<Constraint> = config
-            .playlist_layout()
-            .iter()
-            .map(|layout| Constraint::Percentage(layout.1))
-            .collect();
-
-        // let table = Table::new(vec![Row::default()]);
-        let max_items = mpd.status().queue_len as usize;
+        let max_items = mpd.queue().map_or(0, Vec::len);
         Self {
             show_popup: false,
             tick_rate: Duration::from_millis(250),
-            tab_titles: vec!["Queue", "Browse"],
+            tab_titles: vec!["Queue", "Browse", "Library", "Playlists", "Search"],
             tab_index: 0,
             state,
+            filter: QueueFilter::new(config.playlist_layout()),
+            playlist_chooser: None,
+            browser: Browser::default(),
+            library: Library::new(config.library()),
+            playlists: StoredPlaylists::default(),
+            search: Search::default(),
+            finder: Finder::new(config.connection().clone()),
+            prompt: None,
+            command_line: CommandLine::default(),
             max_items,
-            header,
-            widths,
-            // table,
+            queue_version: mpd.queue_version(),
+            areas: Areas::default(),
+            last_click: None,
+            last_sort: None,
+            grouped: false,
+            history: History::new(config.undo().depth()),
         }
     }
 
     pub(crate) fn switch(&mut self, mpd: &mut Mpd) {
-        let selected = self.state.selected().unwrap() as u32;
-        mpd.client_mut().switch(selected).unwrap();
+        let Some(selected) = self.state.selected() else {
+            return;
+        };
+        let selected = selected as u32;
+        mpd.command(|client| client.switch(selected));
+    }
+
+    /// Deletes the marked songs or the one under the cursor
+    pub(crate) fn delete(&mut self, mpd: &mut Mpd) {
+        let commands: Vec<String> = self
+            .selection_ids(mpd)
+            .into_iter()
+            .map(|id| format!("deleteid {}", id.0))
+            .collect();
+        if commands.is_empty() {
+            return;
+        }
+
+        let selected_id = self.selected_id(mpd);
+        mpd.command_list(&commands);
+        mpd.update(&[Subsystem::Queue]);
+        self.update_queue(mpd, selected_id);
+        self.state.clear_marks();
+    }
+
+    /// Moves the marked songs or the one under the cursor by `delta` positions,
+    /// the cursor stays on its song
+    pub(crate) fn move_selected(&mut self, mpd: &mut Mpd, delta: isize) {
+        self.commit_visual(mpd);
+        let mut positions = self.selection(mpd);
+        let in_bounds = |pos: &usize| {
+            pos.checked_add_signed(delta)
+                .is_some_and(|to| to < self.max_items)
+        };
+        if positions.is_empty() || !positions.iter().all(in_bounds) {
+            return;
+        }
+        // move the outermost song first so the songs do not jump over each other
+        if delta > 0 {
+            positions.reverse();
+        }
+
+        self.edit_queue(mpd, |client| {
+            for pos in positions {
+                client.shift(pos as u32, pos.saturating_add_signed(delta))?;
+            }
+            Ok(())
+        });
+    }
+
+    /// Moves the marked songs or the one under the cursor right after the playing song.
+    /// MPD picks songs by priority in random mode, so there they get the highest one too
+    pub(crate) fn play_next(&mut self, mpd: &mut Mpd) {
+        self.commit_visual(mpd);
+        let current = mpd.status().song.map(|place| place.pos as usize);
+        let mut positions = self.selection(mpd);
+        positions.retain(|&pos| Some(pos) != current);
+        let after_current = |rest: &[usize]| {
+            current
+                .and_then(|current| rest.iter().position(|&pos| pos == current))
+                .map_or(0, |i| i + 1)
+        };
+        let priority = mpd.status().random.then_some(u8::MAX);
+        self.reposition(mpd, &positions, after_current, priority);
+    }
+
+    /// Moves the marked songs or the one under the cursor to the end of the queue,
+    /// dropping their priority so they are not picked early in random mode
+    pub(crate) fn play_last(&mut self, mpd: &mut Mpd) {
+        self.commit_visual(mpd);
+        let positions = self.selection(mpd);
+        self.reposition(mpd, &positions, <[usize]>::len, Some(0));
+    }
+
+    /// Moves the songs at `positions` in front of the song `at` picks from the others
+    fn reposition(
+        &mut self,
+        mpd: &mut Mpd,
+        positions: &[usize],
+        at: impl FnOnce(&[usize]) -> usize,
+        priority: Option<u8>,
+    ) {
+        let Some(queue) = mpd.queue() else {
+            return;
+        };
+        if positions.is_empty() {
+            return;
+        }
+        let mut order: Vec<usize> = (0..queue.len())
+            .filter(|pos| !positions.contains(pos))
+            .collect();
+        let at = at(&order);
+        order.splice(at..at, positions.iter().copied());
+        let priorities = priority.map_or(Vec::new(), |priority| {
+            positions
+                .iter()
+                .filter_map(|&pos| queue.get(pos)?.place)
+                .filter(|place| place.prio != priority)
+                .map(|place| format!("prioid {} {}", priority, place.id.0))
+                .collect()
+        });
+
+        // the priorities go in the same list, the songs are moved and prioritized at once
+        let mut commands = mpd.reorder_commands(&order);
+        commands.extend(priorities);
+        if commands.is_empty() {
+            return;
+        }
+        let selected_id = self.selected_id(mpd);
+        mpd.command_list(&commands);
+        mpd.update(&[Subsystem::Queue]);
+        self.update_queue(mpd, selected_id);
+    }
+
+    /// Reorders the queue by the keys, the cursor stays on its song
+    pub(crate) fn sort_queue(&mut self, mpd: &mut Mpd, keys: &[SortKey]) {
+        let Some(queue) = mpd.queue() else {
+            return;
+        };
+        let order = sort_order(queue, keys);
+        let selected_id = self.selected_id(mpd);
+        mpd.reorder_queue(&order);
+        mpd.update(&[Subsystem::Queue]);
+        self.update_queue(mpd, selected_id);
+        self.last_sort = None;
+    }
+
+    /// Sorts the queue by what the column shows, descending if it was just sorted ascending by it
+    pub(crate) fn sort_by_column(&mut self, mpd: &mut Mpd, config: &Config, column: usize) {
+        let Some(content) = config.playlist_layout().get(column).map(Column::content) else {
+            return;
+        };
+        let descending = self.last_sort == Some((column, false));
+        self.sort_queue(mpd, &[SortKey::new(content.clone(), descending)]);
+        self.last_sort = Some((column, descending));
+    }
+
+    /// Catches up with the queue changed by a command and keeps the change to be undone
+    pub(crate) fn record_queue(
+        &mut self,
+        mpd: &mut Mpd,
+        before: Snapshot,
+        selected_id: Option<Id>,
+    ) {
+        mpd.update(&[Subsystem::Queue]);
+        self.update_queue(mpd, selected_id);
+        self.history.record(mpd, before);
+    }
+
+    pub(crate) fn undo(&mut self, mpd: &mut Mpd) -> Result<(), String> {
+        let selected_id = self.selected_id(mpd);
+        let result = self.history.undo(mpd);
+        self.update_queue(mpd, selected_id);
+        result
+    }
+
+    pub(crate) fn redo(&mut self, mpd: &mut Mpd) -> Result<(), String> {
+        let selected_id = self.selected_id(mpd);
+        let result = self.history.redo(mpd);
+        self.update_queue(mpd, selected_id);
+        result
+    }
+
+    pub(crate) fn set_undo_depth(&mut self, depth: usize) {
+        self.history.set_depth(depth);
+    }
+
+    pub(crate) const fn grouped(&self) -> bool {
+        self.grouped
+    }
+
+    pub(crate) fn set_grouped(&mut self, grouped: bool) {
+        self.grouped = grouped;
+    }
+
+    /// Changes the priority of the marked songs or the one under the cursor
+    pub(crate) fn change_priority(&mut self, mpd: &mut Mpd, priority: impl Fn(u8) -> u8) {
+        self.commit_visual(mpd);
+        let Some(queue) = mpd.queue() else {
+            return;
+        };
+        let priorities: Vec<(Id, u8)> = self
+            .selection(mpd)
+            .into_iter()
+            .filter_map(|pos| queue.get(pos)?.place)
+            .map(|place| (place.id, priority(place.prio)))
+            .collect();
+
+        self.edit_queue(mpd, |client| {
+            for (id, prio) in priorities {
+                client.priority(id, prio)?;
+            }
+            Ok(())
+        });
+    }
+
+    /// Marks or unmarks the song under the cursor and moves to the next one
+    pub(crate) fn toggle_mark(&mut self, mpd: &Mpd) {
+        if let Some(id) = self.selected_id(mpd) {
+            self.state.toggle_mark(id);
+        }
+        self.next();
+    }
+
+    /// Starts the visual mode or marks the range selected in it
+    pub(crate) fn toggle_visual(&mut self, mpd: &Mpd) {
+        if self.state.visual_anchor().is_some() {
+            self.commit_visual(mpd);
+        } else {
+            self.state.set_visual_anchor(self.state.selected());
+        }
+    }
+
+    pub(crate) fn clear_marks(&mut self) {
+        self.state.clear_marks();
+    }
+
+    /// Turns the range selected in the visual mode into marks, since positions change when editing
+    fn commit_visual(&mut self, mpd: &Mpd) {
+        let Some(range) = self.state.visual_range() else {
+            return;
+        };
+        let ids: Vec<Id> = mpd.queue().map_or(Vec::new(), |queue| {
+            queue
+                .get(range)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|song| song.place.map(|place| place.id))
+                .collect()
+        });
+        self.state.mark(ids);
+        self.state.set_visual_anchor(None);
+    }
+
+    /// Positions of the marked songs, or of the one under the cursor if nothing is marked
+    fn selection(&self, mpd: &Mpd) -> Vec<usize> {
+        let range = self.state.visual_range();
+        let marked = self.state.marked();
+        if range.is_none() && marked.is_empty() {
+            return self.state.selected().into_iter().collect();
+        }
+
+        mpd.queue().map_or(Vec::new(), |queue| {
+            queue
+                .iter()
+                .enumerate()
+                .filter(|(pos, song)| {
+                    range.as_ref().is_some_and(|range| range.contains(pos))
+                        || song.place.is_some_and(|place| marked.contains(&place.id))
+                })
+                .map(|(pos, _)| pos)
+                .collect()
+        })
+    }
+
+    fn selection_ids(&self, mpd: &Mpd) -> Vec<Id> {
+        let Some(queue) = mpd.queue() else {
+            return Vec::new();
+        };
+        self.selection(mpd)
+            .into_iter()
+            .filter_map(|pos| queue.get(pos)?.place.map(|place| place.id))
+            .collect()
+    }
+
+    pub(crate) fn open_playlist_chooser(&mut self, mpd: &Mpd) {
+        if mpd
+            .playlists()
+            .is_some_and(|playlists| !playlists.is_empty())
+        {
+            let mut state = ListState::default();
+            state.select(Some(0));
+            self.playlist_chooser = Some(state);
+        }
+    }
+
+    pub(crate) fn close_playlist_chooser(&mut self) {
+        self.playlist_chooser = None;
+    }
+
+    /// Moves the cursor of the playlist chooser by `delta` wrapping around
+    pub(crate) fn playlist_chooser_move(&mut self, mpd: &Mpd, delta: isize) {
+        let len = mpd.playlists().map_or(0, Vec::len);
+        if let Some(state) = self.playlist_chooser.as_mut() {
+            if len == 0 {
+                return;
+            }
+            let selected = state.selected().unwrap_or(0) as isize;
+            state.select(Some((selected + delta).rem_euclid(len as isize) as usize));
+        }
+    }
+
+    /// Adds the marked songs or the one under the cursor to the playlist chosen
+    pub(crate) fn confirm_playlist_chooser(&mut self, mpd: &mut Mpd) {
+        let Some(state) = self.playlist_chooser.take() else {
+            return;
+        };
+        let Some(name) = state
+            .selected()
+            .and_then(|i| mpd.playlists()?.get(i))
+            .map(|playlist| playlist.name.clone())
+        else {
+            return;
+        };
+        self.add_to_playlist(mpd, &name);
+    }
+
+    /// Adds the marked songs or the one under the cursor to the playlist, creating it if there is none
+    pub(crate) fn add_to_playlist(&mut self, mpd: &mut Mpd, name: &str) {
+        self.commit_visual(mpd);
+        let Some(queue) = mpd.queue() else {
+            return;
+        };
+        let songs: Vec<Song> = self
+            .selection(mpd)
+            .into_iter()
+            .filter_map(|pos| queue.get(pos).cloned())
+            .collect();
+
+        mpd.command(|client| {
+            for song in &songs {
+                client.pl_push(name, song)?;
+            }
+            Ok(())
+        });
+        self.state.clear_marks();
+    }
+
+    /// Prompt for saving the marked songs, or the whole queue if nothing is marked, as a playlist
+    pub(crate) fn save_queue_prompt(&mut self, mpd: &Mpd) -> Prompt {
+        match self.marked_songs(mpd) {
+            None => Prompt::text(
+                "Save queue as".to_string(),
+                "",
+                PromptAction::SaveQueue(None),
+            ),
+            Some(songs) => Prompt::text(
+                format!("Save {} marked songs as", songs.len()),
+                "",
+                PromptAction::SaveQueue(Some(songs)),
+            ),
+        }
+    }
+
+    /// Saves the marked songs, or the whole queue if nothing is marked, as a playlist,
+    /// asking first if it would replace one
+    pub(crate) fn save_queue(&mut self, mpd: &mut Mpd, name: &str) {
+        let songs = self.marked_songs(mpd);
+        self.save_queue_as(mpd, name, songs);
+    }
+
+    fn save_queue_as(&mut self, mpd: &mut Mpd, name: &str, songs: Option<Vec<Song>>) {
+        let exists = mpd
+            .playlists()
+            .is_some_and(|playlists| playlists.iter().any(|playlist| playlist.name == name));
+        if exists {
+            self.prompt = Some(Prompt::confirm(
+                format!("Playlist \"{}\" already exists, overwrite it?", name),
+                PromptAction::OverwritePlaylist {
+                    name: name.to_string(),
+                    songs,
+                },
+            ));
+        } else {
+            self.save_playlist(mpd, name, songs.as_deref(), false);
+        }
+    }
+
+    /// Marked songs, `None` if nothing is marked
+    fn marked_songs(&mut self, mpd: &Mpd) -> Option<Vec<Song>> {
+        self.commit_visual(mpd);
+        if self.state.marked().is_empty() {
+            return None;
+        }
+
+        Some(mpd.queue().map_or(Vec::new(), |queue| {
+            self.selection(mpd)
+                .into_iter()
+                .filter_map(|pos| queue.get(pos).cloned())
+                .collect()
+        }))
+    }
+
+    /// Saves the songs, or the whole queue if there are none, as a playlist
+    fn save_playlist(
+        &mut self,
+        mpd: &mut Mpd,
+        name: &str,
+        songs: Option<&[Song]>,
+        overwrite: bool,
+    ) {
+        // an existing playlist is only replaced once the new one is saved in full
+        let target = if overwrite {
+            format!("{}.empirust-tmp", name)
+        } else {
+            name.to_string()
+        };
+        let saved = mpd.command(|client| {
+            let written = match songs {
+                Some(songs) => songs
+                    .iter()
+                    .try_for_each(|song| client.pl_push(target.as_str(), song)),
+                None => client.save(target.as_str()),
+            };
+            if let Err(e) = written {
+                if overwrite {
+                    client.pl_remove(target.as_str()).ok();
+                }
+                return Err(e);
+            }
+            if overwrite {
+                client.pl_remove(name)?;
+                client.pl_rename(target.as_str(), name)?;
+            }
+            Ok(())
+        });
+        if saved.is_some() {
+            self.state.clear_marks();
+            self.playlists.select_name(name);
+        }
     }
 
-    pub(crate) fn tab_next(&mut self) {
-        self.tab_index = (self.tab_index + 1) % self.tab_titles.len();
+    pub(crate) fn playlist_chooser_mut(&mut self) -> Option<&mut ListState> {
+        self.playlist_chooser.as_mut()
+    }
+
+    /// Deletes all the songs but the playing one
+    pub(crate) fn crop(&mut self, mpd: &mut Mpd) {
+        let Some(playing) = mpd.status().song.map(|place| place.pos) else {
+            return;
+        };
+        let len = mpd.status().queue_len;
+        let mut commands = Vec::new();
+        // the tail goes first, so the positions of the head stay the same
+        if playing + 1 < len {
+            commands.push(format!("delete {}:{}", playing + 1, len));
+        }
+        if playing > 0 {
+            commands.push(format!("delete 0:{}", playing));
+        }
+        if commands.is_empty() {
+            return;
+        }
+
+        let selected_id = self.selected_id(mpd);
+        mpd.command_list(&commands);
+        mpd.update(&[Subsystem::Queue]);
+        self.update_queue(mpd, selected_id);
+    }
+
+    pub(crate) fn clear(&mut self, mpd: &mut Mpd) {
+        self.edit_queue(mpd, |client| client.clear());
+    }
+
+    pub(crate) fn shuffle(&mut self, mpd: &mut Mpd) {
+        self.edit_queue(mpd, |client| client.shuffle(..));
+    }
+
+    /// Runs a command editing the queue and catches up with its result right away
+    fn edit_queue(
+        &mut self,
+        mpd: &mut Mpd,
+        f: impl FnOnce(&mut Client<Stream>) -> Result<(), mpd::error::Error>,
+    ) {
+        let selected_id = self.selected_id(mpd);
+        mpd.command(f);
+        mpd.update(&[Subsystem::Queue]);
+        self.update_queue(mpd, selected_id);
+    }
+
+    /// Id of the song under the cursor
+    pub(crate) fn selected_id(&self, mpd: &Mpd) -> Option<Id> {
+        let song = mpd.queue()?.get(self.state.selected()?)?;
+        song.place.map(|place| place.id)
+    }
+
+    /// Catches up with a changed queue, keeping the cursor on the song which was selected
+    pub(crate) fn update_queue(&mut self, mpd: &Mpd, selected_id: Option<Id>) {
+        if self.queue_version == mpd.queue_version() {
+            return;
+        }
+        self.queue_version = mpd.queue_version();
+        self.max_items = mpd.queue().map_or(0, Vec::len);
+
+        let selected = selected_id
+            .and_then(|id| mpd.queue_pos(id))
+            .or_else(|| self.state.selected())
+            .map(|pos| pos.min(self.max_items.saturating_sub(1)));
+        self.state.select(selected.filter(|_| self.max_items > 0));
+        let anchor = self.state.visual_anchor().filter(|_| self.max_items > 0);
+        self.state
+            .set_visual_anchor(anchor.map(|anchor| anchor.min(self.max_items - 1)));
+        if self.filter.active() {
+            self.filter.update(mpd.queue().map_or(&[], Vec::as_slice));
+        }
+    }
+
+    /// Starts typing a pattern to search the queue for
+    pub(crate) fn start_filter(&mut self) {
+        self.filter.start(self.state.selected());
+    }
+
+    /// Finds the songs matching the pattern typed in so far and jumps to the first one
+    pub(crate) fn update_filter(&mut self, mpd: &Mpd) {
+        self.filter.update(mpd.queue().map_or(&[], Vec::as_slice));
+        let origin = self.filter.origin();
+        if let Some(pos) = self.filter.match_from(origin) {
+            self.state.select(Some(pos));
+        }
+    }
+
+    /// Searches the queue for the pattern as if it was typed in
+    pub(crate) fn set_filter(&mut self, mpd: &Mpd, pattern: &str) {
+        self.start_filter();
+        *self.filter.input_mut() = TextInput::new(pattern);
+        self.update_filter(mpd);
+        self.accept_filter();
+    }
+
+    pub(crate) fn accept_filter(&mut self) {
+        self.filter.accept();
+    }
+
+    /// Drops the pattern and goes back to where the cursor was before typing it
+    pub(crate) fn cancel_filter(&mut self) {
+        if let Some(pos) = self.filter.cancel() {
+            self.state
+                .select(Some(pos.min(self.max_items.saturating_sub(1))));
+        }
+    }
+
+    /// Moves the cursor to the next or previous song matching the pattern
+    pub(crate) fn jump_match(&mut self, forward: bool) {
+        if let Some(pos) = self.filter.next_match(self.state.selected(), forward) {
+            self.state.select(Some(pos));
+        }
+    }
+
+    /// Shows only the songs matching the pattern or all of them
+    pub(crate) fn toggle_filter(&mut self) {
+        self.filter.toggle_filtering();
+        // the cursor has to be on one of the rows shown
+        if self.filter.rows().is_some() {
+            let selected = self.state.selected();
+            if let Some(pos) = self.filter.match_from(selected) {
+                self.state.select(Some(pos));
+            }
+        }
+    }
+
+    pub(crate) fn tab_next(&mut self, mpd: &mut Mpd) {
+        self.select_tab(mpd, (self.tab_index + 1) % self.tab_titles.len());
+    }
+
+    pub(crate) fn select_tab(&mut self, mpd: &mut Mpd, index: usize) {
+        self.tab_index = index;
+        match self.tab_index {
+            BROWSE_TAB => self.browser.load(mpd),
+            LIBRARY_TAB => self.library.load(mpd),
+            PLAYLISTS_TAB => self.playlists.load(mpd),
+            _ => (),
+        }
     }
 
     // pub(crate) fn tab_previous(&mut self) {
@@ -95,60 +681,14 @@ impl<'app> App<'app> {
         self.tick_rate
     }
 
-    // pub(crate) fn table_update(&'app mut self, mpd: &Mpd, config: &Config) {
-    //     let table = self.gen_table(mpd, config);
-    //     self.table = table;
-    // }
-
-    pub(crate) fn gen_table(&'app self, mpd: &Mpd, config: &Config) -> Table<'app> {
-        // setup rows
-        let songs = mpd.queue().unwrap();
-
-        let style_playing = config.styles().playing();
-        let style_normal = config.styles().normal();
-        let curr_playing_pos = mpd.curr_playing_pos();
-
-        let mut i = 0;
-        let rows = songs.iter().map(|song| -> Row {
-            let row = config.playlist_layout().iter().map(|layout| -> String {
-                match layout.0 {
-                    config::PlaylistLayout::File => song.file.clone(),
-                    config::PlaylistLayout::Title => song.title.clone().unwrap_or_default(),
-                    config::PlaylistLayout::Duration => song
-                        .duration
-                        .map(|item| {
-                            crate::ui::human_formated_time(item.num_seconds().try_into().unwrap())
-                        })
-                        .unwrap_or_default(),
-                    config::PlaylistLayout::Album => {
-                        song.tags.get("Album").cloned().unwrap_or_default()
-                    }
-                    config::PlaylistLayout::Artist => {
-                        song.tags.get("Artist").cloned().unwrap_or_default()
-                    }
-                    config::PlaylistLayout::Track => {
-                        song.tags.get("Track").cloned().unwrap_or_default()
-                    }
-                }
-            });
-            let style = if curr_playing_pos == i {
-                style_playing
-            } else {
-                style_normal
-            };
-            i += 1;
-            Row::new(row).style(style)
-        });
-
-        Table::new(rows)
-            .header(self.header.clone())
-            .block(Block::default().borders(Borders::TOP))
-            .highlight_style(config.styles().selected())
-            .widths(&self.widths)
-    }
-
     /// Selecet next item in Queue
     pub(crate) fn next(&mut self) {
+        if self.filter.rows().is_some() {
+            return self.jump_match(true);
+        }
+        if self.max_items == 0 {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.max_items - 1 {
@@ -164,6 +704,12 @@ impl<'app> App<'app> {
 
     /// Selecet previous item in Queue
     pub(crate) fn previous(&mut self) {
+        if self.filter.rows().is_some() {
+            return self.jump_match(false);
+        }
+        if self.max_items == 0 {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -177,6 +723,52 @@ impl<'app> App<'app> {
         self.state.select(Some(i));
     }
 
+    /// Selects the song clicked on, plays it if it was clicked twice in a row
+    pub(crate) fn click_queue(&mut self, mpd: &mut Mpd, x: u16, y: u16) {
+        let Some(row) = self.state.row_at(x, y) else {
+            return;
+        };
+        let pos = match self.filter.rows() {
+            Some(rows) => rows.get(row).copied(),
+            None => Some(row),
+        };
+        let Some(pos) = pos.filter(|pos| *pos < self.max_items) else {
+            return;
+        };
+
+        self.state.select(Some(pos));
+        let double = self
+            .last_click
+            .is_some_and(|(at, last)| last == pos && at.elapsed() < DOUBLE_CLICK);
+        if double {
+            self.switch(mpd);
+            self.last_click = None;
+        } else {
+            self.last_click = Some((Instant::now(), pos));
+        }
+    }
+
+    /// Moves the cursor by `lines` without wrapping around, for the mouse wheel
+    pub(crate) fn scroll(&mut self, lines: isize) {
+        let rows = self.filter.rows();
+        let len = rows.map_or(self.max_items, <[usize]>::len);
+        if len == 0 {
+            return;
+        }
+        let selected = self.state.selected().unwrap_or(0);
+        let row = rows.map_or(selected, |rows| rows.partition_point(|pos| *pos < selected));
+        let row = row.saturating_add_signed(lines).min(len - 1);
+        self.state.select(Some(rows.map_or(row, |rows| rows[row])));
+    }
+
+    pub(crate) const fn areas(&self) -> &Areas {
+        &self.areas
+    }
+
+    pub(crate) fn areas_mut(&mut self) -> &mut Areas {
+        &mut self.areas
+    }
+
     pub(crate) fn tab_titles(&self) -> &[&str] {
         self.tab_titles.as_ref()
     }
@@ -185,11 +777,134 @@ impl<'app> App<'app> {
         self.tab_index
     }
 
-    pub(crate) const fn state(&self) -> &TableState {
-        &self.state
+    pub(crate) fn state_mut(&mut self) -> &mut QueueState {
+        &mut self.state
+    }
+
+    pub(crate) const fn filter(&self) -> &QueueFilter {
+        &self.filter
+    }
+
+    pub(crate) fn filter_mut(&mut self) -> &mut QueueFilter {
+        &mut self.filter
+    }
+
+    /// Whether the keys go to the pattern searched for in the queue
+    pub(crate) const fn filter_editing(&self) -> bool {
+        self.tab_index == QUEUE_TAB && self.filter.editing()
+    }
+
+    pub(crate) fn browser_mut(&mut self) -> &mut Browser {
+        &mut self.browser
+    }
+
+    pub(crate) fn library_mut(&mut self) -> &mut Library {
+        &mut self.library
+    }
+
+    pub(crate) fn playlists_mut(&mut self) -> &mut StoredPlaylists {
+        &mut self.playlists
     }
 
-    pub(crate) fn set_state(&mut self, state: TableState) {
-        self.state = state;
+    pub(crate) fn search_mut(&mut self) -> &mut Search {
+        &mut self.search
+    }
+
+    /// Searches for the query as if it was typed in on the search tab
+    pub(crate) fn set_search(&mut self, mpd: &mut Mpd, scope: SearchScope, query: &str) {
+        self.search.set_query(scope, query);
+        self.search.run(mpd);
+    }
+
+    /// Starts the library over after its settings were changed
+    pub(crate) fn reset_library(&mut self, mpd: &mut Mpd, config: &Config) {
+        self.library = Library::new(config.library());
+        if self.tab_index == LIBRARY_TAB {
+            self.library.load(mpd);
+        }
+    }
+
+    pub(crate) const fn command_line(&self) -> &CommandLine {
+        &self.command_line
+    }
+
+    pub(crate) fn command_line_mut(&mut self) -> &mut CommandLine {
+        &mut self.command_line
+    }
+
+    pub(crate) fn finder_mut(&mut self) -> &mut Finder {
+        &mut self.finder
+    }
+
+    /// Whether the keys go to the query of the search
+    pub(crate) const fn search_editing(&self) -> bool {
+        self.tab_index == SEARCH_TAB && self.search.editing()
+    }
+
+    pub(crate) fn open_prompt(&mut self, prompt: Option<Prompt>) {
+        if prompt.is_some() {
+            self.prompt = prompt;
+        }
+    }
+
+    /// Prompt for the crossfade in seconds, starting with the current one
+    pub(crate) fn open_crossfade_prompt(&mut self, mpd: &Mpd) {
+        let seconds = mpd
+            .status()
+            .crossfade
+            .map_or(0, |crossfade| crossfade.as_secs());
+        self.prompt = Some(Prompt::text(
+            "Crossfade in seconds".to_string(),
+            &seconds.to_string(),
+            PromptAction::Crossfade,
+        ));
+    }
+
+    pub(crate) fn close_prompt(&mut self) {
+        self.prompt = None;
+    }
+
+    pub(crate) const fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
+    pub(crate) fn prompt_mut(&mut self) -> Option<&mut Prompt> {
+        self.prompt.as_mut()
+    }
+
+    pub(crate) fn rename_playlist(&mut self, mpd: &mut Mpd, name: &str, to: &str) {
+        if !to.is_empty() && to != name {
+            mpd.command(|client| client.pl_rename(name, to));
+            self.playlists.select_name(to);
+        }
+    }
+
+    /// Runs the action of the prompt
+    pub(crate) fn confirm_prompt(&mut self, mpd: &mut Mpd) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        let (action, input) = prompt.into_action();
+        let text = input.as_ref().map_or("", |input| input.value());
+
+        match action {
+            PromptAction::DeletePlaylist(name) => {
+                mpd.command(|client| client.pl_remove(name.as_str()));
+            }
+            PromptAction::RenamePlaylist(name) => self.rename_playlist(mpd, &name, text),
+            PromptAction::RemoveSong { playlist, pos } => {
+                mpd.command(|client| client.pl_delete(playlist.as_str(), pos));
+            }
+            PromptAction::SaveQueue(_) if text.is_empty() => (),
+            PromptAction::SaveQueue(songs) => self.save_queue_as(mpd, text, songs),
+            PromptAction::OverwritePlaylist { name, songs } => {
+                self.save_playlist(mpd, &name, songs.as_deref(), true);
+            }
+            PromptAction::Crossfade => {
+                if let Ok(seconds) = text.trim().parse() {
+                    mpd.set_crossfade(seconds);
+                }
+            }
+        }
     }
 }
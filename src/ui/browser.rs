@@ -0,0 +1,127 @@
+//! Browser of MPD's database directories
+
+use crate::mpd::{AddMode, Entry, Mpd};
+use std::collections::HashMap;
+use tui::widgets::ListState;
+
+#[derive(Debug, Default)]
+/// Directory being browsed along with the listings of the ones visited before
+pub struct Browser {
+    /// Path of the directory relative to the music directory, empty for the root one
+    path: String,
+    /// Listings of the directories by their path
+    cache: HashMap<String, Vec<Entry>>,
+    /// Entry selected in the directories left, to come back to it
+    selected: HashMap<String, usize>,
+    state: ListState,
+}
+
+impl Browser {
+    /// Lists the current directory if it was not done yet
+    pub(crate) fn load(&mut self, mpd: &mut Mpd) {
+        if self.cache.contains_key(&self.path) {
+            return;
+        }
+
+        if let Some(entries) = mpd.list_directory(&self.path) {
+            self.cache.insert(self.path.clone(), entries);
+        }
+        let len = self.entries().len();
+        let selected = self.state.selected().unwrap_or(0);
+        self.state.select((len > 0).then(|| selected.min(len - 1)));
+    }
+
+    /// Drops every listing since the database was changed, relisting the current directory if it was
+    pub(crate) fn invalidate(&mut self, mpd: &mut Mpd) {
+        let loaded = self.cache.contains_key(&self.path);
+        self.cache.clear();
+        if loaded {
+            self.load(mpd);
+        }
+    }
+
+    pub(crate) fn entries(&self) -> &[Entry] {
+        self.cache.get(&self.path).map_or(&[][..], Vec::as_slice)
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn state_mut(&mut self) -> &mut ListState {
+        &mut self.state
+    }
+
+    fn selected_entry(&self) -> Option<&Entry> {
+        self.entries().get(self.state.selected()?)
+    }
+
+    pub(crate) fn next(&mut self) {
+        let len = self.entries().len();
+        if len == 0 {
+            return;
+        }
+        let i = self.state.selected().map_or(0, |i| (i + 1) % len);
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let len = self.entries().len();
+        if len == 0 {
+            return;
+        }
+        let i = self
+            .state
+            .selected()
+            .map_or(0, |i| i.checked_sub(1).unwrap_or(len - 1));
+        self.state.select(Some(i));
+    }
+
+    /// Enters the selected directory
+    pub(crate) fn open(&mut self, mpd: &mut Mpd) {
+        let Some(Entry::Directory(path)) = self.selected_entry() else {
+            return;
+        };
+        let path = path.clone();
+        self.change_dir(mpd, path);
+    }
+
+    /// Goes up to the parent directory
+    pub(crate) fn back(&mut self, mpd: &mut Mpd) {
+        if self.path.is_empty() {
+            return;
+        }
+        let parent = self
+            .path
+            .rsplit_once('/')
+            .map_or_else(String::new, |(parent, _)| parent.to_string());
+        self.change_dir(mpd, parent);
+    }
+
+    fn change_dir(&mut self, mpd: &mut Mpd, path: String) {
+        if let Some(selected) = self.state.selected() {
+            self.selected.insert(self.path.clone(), selected);
+        }
+
+        self.path = path;
+        self.state = ListState::default();
+        self.state
+            .select(Some(self.selected.get(&self.path).copied().unwrap_or(0)));
+        self.load(mpd);
+    }
+
+    /// Adds the selected song or every song inside the selected directory to the queue
+    pub(crate) fn add(&self, mpd: &mut Mpd, mode: AddMode) {
+        let songs = match self.selected_entry() {
+            Some(Entry::Song(song)) => vec![song.clone()],
+            Some(Entry::Directory(path)) => {
+                let Some(songs) = mpd.songs_in_directory(path) else {
+                    return;
+                };
+                songs
+            }
+            None => return,
+        };
+        mpd.add_songs(&songs, mode);
+    }
+}
@@ -0,0 +1,150 @@
+//! Line commands are typed in after `:`, with completion and history
+
+use crate::ui::text_input::TextInput;
+use crossterm::event::KeyEvent;
+
+/// How many lines run before are kept
+const HISTORY_SIZE: usize = 100;
+
+#[derive(Debug)]
+/// Candidates the word at the end of the line can be completed to
+struct Completion {
+    /// Line before the word
+    head: String,
+    candidates: Vec<String>,
+    /// Candidate put in place of the word, `None` until cycling through them
+    index: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    open: bool,
+    input: TextInput,
+    /// Lines run before, the latest last
+    history: Vec<String>,
+    /// Line of the history shown, `None` while typing a new one
+    history_pos: Option<usize>,
+    /// Line being typed before going through the history
+    draft: String,
+    /// Is `None` until tab is pressed, and again once the line is edited
+    completion: Option<Completion>,
+}
+
+impl CommandLine {
+    pub(crate) fn open(&mut self) {
+        self.open = true;
+        self.input = TextInput::default();
+        self.history_pos = None;
+        self.completion = None;
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Closes the command line, returns the line typed in and keeps it in the history
+    pub(crate) fn take(&mut self) -> String {
+        self.close();
+        let line = std::mem::take(&mut self.input).value().trim().to_string();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY_SIZE {
+                self.history.remove(0);
+            }
+        }
+        line
+    }
+
+    /// Edits the line, dropping the completion
+    pub(crate) fn handle_key(&mut self, key: KeyEvent) {
+        if self.input.handle_key(key) {
+            self.completion = None;
+        }
+    }
+
+    /// Shows the line run before the one shown
+    pub(crate) fn history_prev(&mut self) {
+        let pos = match self.history_pos {
+            Some(pos) => pos.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.input.value().to_string();
+                self.history.len() - 1
+            }
+        };
+        self.show_history(Some(pos));
+    }
+
+    /// Shows the line run after the one shown, or the one typed before going through the history
+    pub(crate) fn history_next(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        self.show_history(Some(pos + 1).filter(|pos| *pos < self.history.len()));
+    }
+
+    fn show_history(&mut self, pos: Option<usize>) {
+        self.history_pos = pos;
+        let line = pos.map_or(&self.draft, |pos| &self.history[pos]);
+        self.input = TextInput::new(line);
+        self.completion = None;
+    }
+
+    /// Completes the word at the end of the line as far as all its candidates agree,
+    /// then cycles through them. `find` tells where the word starts and its candidates
+    pub(crate) fn complete(&mut self, find: impl FnOnce(&str) -> (usize, Vec<String>)) {
+        if let Some(completion) = self.completion.as_mut() {
+            if completion.candidates.is_empty() {
+                return;
+            }
+            let index = completion
+                .index
+                .map_or(0, |i| (i + 1) % completion.candidates.len());
+            completion.index = Some(index);
+            let line = format!("{}{}", completion.head, completion.candidates[index]);
+            self.input = TextInput::new(&line);
+            return;
+        }
+
+        let (start, candidates) = find(self.input.value());
+        let head = self.input.value()[..start].to_string();
+        if let Some(prefix) = common_prefix(&candidates) {
+            self.input = TextInput::new(&format!("{}{}", head, prefix));
+        }
+        // a single candidate is done with, the next tab completes after it
+        if candidates.len() != 1 {
+            self.completion = Some(Completion {
+                head,
+                candidates,
+                index: None,
+            });
+        }
+    }
+
+    pub(crate) const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub(crate) const fn input(&self) -> &TextInput {
+        &self.input
+    }
+
+    /// Candidates of the completion along with the one put in the line
+    pub(crate) fn candidates(&self) -> Option<(&[String], Option<usize>)> {
+        let completion = self.completion.as_ref()?;
+        Some((&completion.candidates, completion.index))
+    }
+}
+
+/// Longest prefix all the candidates start with
+fn common_prefix(candidates: &[String]) -> Option<&str> {
+    let (first, rest) = candidates.split_first()?;
+    let len = rest.iter().fold(first.len(), |len, candidate| {
+        first[..len]
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(candidate.len()), |((i, _), _)| i)
+    });
+    Some(&first[..len])
+}
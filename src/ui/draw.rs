@@ -2,15 +2,19 @@
 
 use crate::{
     config::Config,
-    mpd::Mpd,
-    ui::{app::App, human_formated_time},
+    mpd::{song_tag, Entry, ModeState, Mpd},
+    ui::{
+        app::{App, Areas, BROWSE_TAB, LIBRARY_TAB, PLAYLISTS_TAB, QUEUE_TAB, SEARCH_TAB},
+        human_formated_time,
+        queue::{align_cell, column_widths, fit_widths, format_cell, Queue},
+    },
 };
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Cell, Gauge, List, ListItem, Row, Table, Tabs},
+    widgets::{Block, Borders, Cell, Gauge, List, ListItem, Paragraph, Row, Table, Tabs, Wrap},
     Frame,
 };
 
@@ -31,23 +35,53 @@ where
         .iter()
         .map(|t| Spans::from(Span::styled(*t, Style::default())))
         .collect();
+    // every title is padded by a space on both sides and followed by a divider
+    let mut x = chunks[0].x;
+    let tab_areas = app
+        .tab_titles()
+        .iter()
+        .map(|title| {
+            let width = title.chars().count() as u16 + 2;
+            let area = Rect::new(x, chunks[0].y, width, 1).intersection(chunks[0]);
+            x = x.saturating_add(width + 1);
+            area
+        })
+        .collect();
     let tabs = Tabs::new(tab_titles)
         .select(app.tab_index())
         .style(Style::default().fg(Color::DarkGray))
         .highlight_style(config.styles().tab_selected());
     f.render_widget(tabs, chunks[0]);
+    // the areas of the other parts are only set again by the tabs drawing them
+    *app.areas_mut() = Areas {
+        tabs: tab_areas,
+        ..Areas::default()
+    };
 
     match app.tab_index() {
-        0 => draw_tab_one(f, app, chunks[1], config, mpd),
-        1 => draw_tab_two(f, app, chunks[1], config, mpd),
+        QUEUE_TAB => draw_tab_one(f, app, chunks[1], config, mpd),
+        BROWSE_TAB => draw_tab_two(f, app, chunks[1], config, mpd),
+        LIBRARY_TAB => draw_tab_three(f, app, chunks[1], config, mpd),
+        PLAYLISTS_TAB => draw_tab_four(f, app, chunks[1], config, mpd),
+        SEARCH_TAB => draw_tab_five(f, app, chunks[1], config, mpd),
         _ => {}
     }
 
+    if !mpd.connected() {
+        draw_disconnected(f, mpd);
+        return;
+    }
+
+    draw_playlist_chooser(f, app, config, mpd);
+    draw_finder(f, app, config);
+    draw_prompt(f, app, config);
+    draw_command_line(f, app, config);
+
     if !app.show_popup {
         return;
     }
 
-    let area = calculate_area_for_popup(40, 40, size);
+    let area = calculate_area_for_popup(60, 60, size);
     // clears out the background
     f.render_widget(tui::widgets::Clear, area);
 
@@ -58,11 +92,231 @@ where
 
     let table = Table::new(rows)
         .block(Block::default().title("Help").borders(Borders::ALL))
-        .widths(&[Constraint::Percentage(20), Constraint::Percentage(80)]);
+        .widths(&[Constraint::Percentage(30), Constraint::Percentage(70)]);
 
     f.render_widget(table, area);
 }
 
+/// Renders an error message in place of the UI
+pub fn draw_error<B>(f: &mut Frame<B>, message: &str)
+where
+    B: Backend,
+{
+    let area = calculate_area_for_popup(60, 30, f.size());
+    let text = vec![
+        Spans::from(Span::styled(message, Style::default().fg(Color::Red))),
+        Spans::from(""),
+        Spans::from("Press any key to quit"),
+    ];
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().title("Error").borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(paragraph, area);
+}
+
+fn draw_disconnected<B>(f: &mut Frame<B>, mpd: &Mpd)
+where
+    B: Backend,
+{
+    let area = calculate_fixed_area(60, 5, f.size());
+    f.render_widget(tui::widgets::Clear, area);
+
+    let text = vec![
+        Spans::from(Span::styled(
+            format!("Disconnected from MPD at {}", mpd.connection()),
+            Style::default().fg(Color::Red),
+        )),
+        Spans::from(format!(
+            "Retrying in {}s…",
+            mpd.reconnect_in().as_secs_f32().ceil()
+        )),
+    ];
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().title("Connection").borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(paragraph, area);
+}
+
+/// Renders the popup for choosing a playlist to add songs to, if it is open
+fn draw_playlist_chooser<B>(f: &mut Frame<B>, app: &mut App, config: &Config, mpd: &Mpd)
+where
+    B: Backend,
+{
+    let Some(state) = app.playlist_chooser_mut() else {
+        return;
+    };
+
+    let area = calculate_area_for_popup(40, 40, f.size());
+    f.render_widget(tui::widgets::Clear, area);
+
+    let items: Vec<ListItem> = mpd
+        .playlists()
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .map(|playlist| ListItem::new(playlist.name.clone()))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Add to playlist")
+                .borders(Borders::ALL),
+        )
+        .style(config.styles().normal())
+        .highlight_style(config.styles().selected());
+    f.render_stateful_widget(list, area, state);
+}
+
+/// Renders the fuzzy finder with the pattern typed in and the best matches, if it is open
+fn draw_finder<B>(f: &mut Frame<B>, app: &mut App, config: &Config)
+where
+    B: Backend,
+{
+    let finder = app.finder_mut();
+    if !finder.is_open() {
+        return;
+    }
+
+    let area = calculate_area_for_popup(60, 60, f.size());
+    f.render_widget(tui::widgets::Clear, area);
+
+    let title = match (finder.error(), finder.total()) {
+        (Some(error), _) => format!("Find: {}", error),
+        (None, Some(total)) => format!("Find ({}/{})", finder.matched(), total),
+        (None, None) => "Find (loading…)".to_string(),
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(config.styles().normal());
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(inner);
+
+    f.render_widget(Paragraph::new("> "), chunks[0]);
+    let input_area = Rect::new(
+        chunks[0].x + 2,
+        chunks[0].y,
+        chunks[0].width.saturating_sub(2),
+        1,
+    );
+    f.render_widget(finder.input(), input_area);
+    let (x, y) = finder.input().cursor_position(input_area);
+    f.set_cursor(x, y);
+
+    let (songs, state) = finder.results_mut();
+    let items: Vec<ListItem> = songs
+        .iter()
+        .map(|song| {
+            let artist = song.artist.as_deref().unwrap_or_default();
+            let album = song_tag(song, "Album").unwrap_or_default();
+            let title = song.title.as_deref().unwrap_or(&song.file);
+            ListItem::new(format!("{} - {} - {}", artist, album, title))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(config.styles().selected());
+    f.render_stateful_widget(list, chunks[1], state);
+}
+
+/// Renders the prompt along with the text typed in it, if there is one
+fn draw_prompt<B>(f: &mut Frame<B>, app: &App, config: &Config)
+where
+    B: Backend,
+{
+    let Some(prompt) = app.prompt() else {
+        return;
+    };
+
+    let area = calculate_fixed_area(60, 3, f.size());
+    f.render_widget(tui::widgets::Clear, area);
+    let block = Block::default()
+        .title(prompt.message())
+        .borders(Borders::ALL)
+        .style(config.styles().normal());
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    match prompt.input() {
+        Some(input) => {
+            f.render_widget(input, inner);
+            let (x, y) = input.cursor_position(inner);
+            f.set_cursor(x, y);
+        }
+        None => f.render_widget(Paragraph::new("y/n"), inner),
+    }
+}
+
+/// Command line over the bottom row, with the candidates of the completion above it
+fn draw_command_line<B>(f: &mut Frame<B>, app: &App, config: &Config)
+where
+    B: Backend,
+{
+    let command_line = app.command_line();
+    let size = f.size();
+    if !command_line.is_open() || size.height < 2 {
+        return;
+    }
+
+    let area = Rect::new(size.x, size.bottom() - 1, size.width, 1);
+    f.render_widget(tui::widgets::Clear, area);
+    f.render_widget(Paragraph::new(":"), area);
+    let input_area = Rect {
+        x: area.x + 1,
+        width: area.width.saturating_sub(1),
+        ..area
+    };
+    f.render_widget(command_line.input(), input_area);
+    let (x, y) = command_line.input().cursor_position(input_area);
+    f.set_cursor(x, y);
+
+    let Some((candidates, index)) = command_line.candidates() else {
+        return;
+    };
+    if candidates.len() < 2 {
+        return;
+    }
+    let spans: Vec<Span> = candidates
+        .iter()
+        .enumerate()
+        .flat_map(|(i, candidate)| {
+            let style = if index == Some(i) {
+                config.styles().selected()
+            } else {
+                config.styles().normal()
+            };
+            // paths are shown from their last part on
+            let candidate = candidate.trim_end();
+            let start = candidate
+                .trim_end_matches('/')
+                .rfind('/')
+                .map_or(0, |i| i + 1);
+            [Span::styled(&candidate[start..], style), Span::raw("  ")]
+        })
+        .collect();
+    let area = Rect {
+        y: area.y - 1,
+        ..area
+    };
+    f.render_widget(tui::widgets::Clear, area);
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+/// Area of the given size in the middle of `area`, shrunk if it does not fit
+fn calculate_fixed_area(width: u16, height: u16, area: Rect) -> Rect {
+    let (width, height) = (area.width.min(width), area.height.min(height));
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
 fn calculate_area_for_popup(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -110,24 +364,58 @@ where
     draw_progressbar(f, app, chunks[2], config, mpd);
 }
 
-// FIXME: this func is very slow and is CPU eater
 fn draw_queue<B>(f: &mut Frame<B>, app: &mut App, area: Rect, config: &Config, mpd: &Mpd)
 where
     B: Backend,
 {
+    let filter = app.filter();
+    let show_filter = filter.editing() || filter.active();
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(5)].as_ref())
+        .constraints(
+            [
+                Constraint::Min(5),
+                Constraint::Length(u16::from(show_filter)),
+            ]
+            .as_ref(),
+        )
         .split(area);
 
-    let table = app.gen_table(mpd, config);
-    // let table = app.table.clone();
-    let mut state = app.state().clone();
-    f.render_stateful_widget(table, chunks[0], &mut state);
-    app.set_state(state);
+    if filter.editing() {
+        let line = chunks[1];
+        f.render_widget(Paragraph::new("/"), line);
+        let input_area = Rect::new(line.x + 1, line.y, line.width.saturating_sub(1), 1);
+        f.render_widget(filter.input(), input_area);
+        let (x, y) = filter.input().cursor_position(input_area);
+        f.set_cursor(x, y);
+    } else if filter.active() {
+        let status = format!(
+            "/{} ({} matches{})",
+            filter.pattern(),
+            filter.matches().len(),
+            if filter.rows().is_some() {
+                ", filtered"
+            } else {
+                ""
+            },
+        );
+        f.render_widget(Paragraph::new(status), chunks[1]);
+    }
+
+    let songs = mpd.queue().map_or(&[][..], Vec::as_slice);
+    let rows = filter.rows().map(<[usize]>::to_vec);
+    let pattern = filter.active().then(|| filter.pattern().to_string());
+    let queue = Queue::new(songs, mpd.queue_version(), config)
+        .playing(mpd.status().song.map(|place| place.pos as usize))
+        .status(mpd.status())
+        .rows(rows.as_deref())
+        .highlight(pattern.as_deref())
+        .grouped(app.grouped())
+        .block(Block::default().borders(Borders::TOP));
+    f.render_stateful_widget(queue, chunks[0], app.state_mut());
 }
 
-fn draw_progressbar<B>(f: &mut Frame<B>, _app: &mut App, area: Rect, config: &Config, mpd: &Mpd)
+fn draw_progressbar<B>(f: &mut Frame<B>, app: &mut App, area: Rect, config: &Config, mpd: &Mpd)
 where
     B: Backend,
 {
@@ -143,71 +431,298 @@ where
         )
         .split(area);
 
-    let label: (String, String) =
-        mpd.curr_song()
-            .as_ref()
-            .map_or((String::new(), String::new()), |song| {
-                let artist = song
-                    .tags
-                    .get("Artist")
-                    .unwrap_or(&String::new())
-                    .to_string();
-                let title = song
-                    .title
-                    .as_ref()
-                    .map_or(String::new(), std::clone::Clone::clone);
-                (artist, title)
-            });
-
-    let label = Block::default()
-        .title(Span::raw(format!("{} - {}", label.0, label.1)))
-        .borders(Borders::TOP);
+    let label = mpd.curr_song().map_or_else(Spans::default, |song| {
+        config
+            .formats()
+            .now_playing()
+            .render(song, Some(mpd.status()))
+    });
+
+    let label = Block::default().title(label).borders(Borders::TOP);
     f.render_widget(label, chunks[0]);
 
     let volume = mpd.status().volume;
-    let status = Block::default().title(Span::styled(
-        format!("Volume: {}%", volume),
-        Style::default().fg(Color::Gray),
-    ));
+    let status = match mpd.error() {
+        Some(error) => Span::styled(error.to_string(), Style::default().fg(Color::Red)),
+        None => Span::styled(
+            format!("Volume: {}%", volume),
+            Style::default().fg(Color::Gray),
+        ),
+    };
+    app.areas_mut().volume = Rect {
+        width: status.width() as u16,
+        ..chunks[1]
+    };
+    let status = Block::default().title(status);
     f.render_widget(status, chunks[1]);
+    let modes = Paragraph::new(mode_indicators(config, mpd)).alignment(Alignment::Right);
+    f.render_widget(modes, chunks[1]);
 
-    let progress: (String, u16) = mpd.status().time.map_or((String::new(), 0), |time| {
-        let elapsed = time.0.num_seconds() as u16;
-        let duration = time.1.num_seconds() as u16;
+    let progress: (String, u16) = mpd.elapsed().map_or((String::new(), 0), |time| {
+        let elapsed = time.0.as_secs() as u16;
+        let duration = time.1.as_secs() as u16;
         let label = format!(
             "{}/{}",
             human_formated_time(elapsed),
             human_formated_time(duration),
         );
-        (label, (elapsed * 100 / duration))
+        let percent = (u32::from(elapsed) * 100)
+            .checked_div(u32::from(duration))
+            .unwrap_or(0);
+        (label, percent.min(100) as u16)
     });
     let progress = Gauge::default()
         .gauge_style(config.styles().progress())
         .label(progress.0)
         .percent(progress.1);
     f.render_widget(progress, chunks[2]);
+    app.areas_mut().progress = chunks[2];
+}
+
+/// `[rzsc]` for repeat, random, single and consume, followed by the crossfade if it is set
+fn mode_indicators<'a>(config: &Config, mpd: &Mpd) -> Spans<'a> {
+    let styles = config.styles();
+    let status = mpd.status();
+    let flag = |letter: &'a str, state: ModeState| match state {
+        ModeState::Off => Span::styled("-", styles.mode_off()),
+        ModeState::On => Span::styled(letter, styles.mode_on()),
+        ModeState::Oneshot => Span::styled(letter, styles.mode_oneshot()),
+    };
+    let on = |on: bool| if on { ModeState::On } else { ModeState::Off };
+
+    let mut spans = vec![
+        Span::raw("["),
+        flag("r", on(status.repeat)),
+        flag("z", on(status.random)),
+        flag("s", mpd.single()),
+        flag("c", mpd.consume()),
+        Span::raw("]"),
+    ];
+    if let Some(crossfade) = status.crossfade.filter(|crossfade| !crossfade.is_zero()) {
+        spans.push(Span::styled(
+            format!(" x{}s", crossfade.as_secs()),
+            styles.mode_on(),
+        ));
+    }
+    Spans::from(spans)
 }
 // }}}
 
 // {{{ 2st tab
-fn draw_tab_two<B>(f: &mut Frame<B>, _app: &mut App, area: Rect, _config: &Config, mpd: &Mpd)
+fn draw_tab_two<B>(f: &mut Frame<B>, app: &mut App, area: Rect, config: &Config, _mpd: &Mpd)
 where
     B: Backend,
 {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0)].as_ref())
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
         .split(area);
 
-    if let Some(items) = mpd.playlists() {
-        let items: Vec<ListItem> = items
-            .iter()
-            .map(|i| ListItem::new(i.name.clone()).style(Style::default()))
-            .collect();
-        let items = List::new(items)
-            .block(Block::default().borders(Borders::TOP))
-            .highlight_style(Style::default());
-        f.render_widget(items, chunks[0]);
+    let browser = app.browser_mut();
+    let breadcrumb: Vec<&str> = std::iter::once("/")
+        .chain(browser.path().split('/').filter(|dir| !dir.is_empty()))
+        .collect();
+    let breadcrumb = Paragraph::new(breadcrumb.join(" > ")).style(config.styles().normal());
+    f.render_widget(breadcrumb, chunks[0]);
+
+    let layout = config.playlist_layout();
+    let songs = browser.entries().iter().filter_map(|entry| match entry {
+        Entry::Song(song) => Some(song),
+        Entry::Directory(_) => None,
+    });
+    let widths = column_widths(
+        layout,
+        chunks[1].width.saturating_sub(2),
+        &fit_widths(layout, songs),
+    );
+    let items: Vec<ListItem> = browser
+        .entries()
+        .iter()
+        .map(|entry| match entry {
+            Entry::Directory(path) => {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                ListItem::new(format!("{}/", name))
+                    .style(Style::default().add_modifier(Modifier::BOLD))
+            }
+            Entry::Song(song) => {
+                let row: Vec<String> = layout
+                    .iter()
+                    .zip(&widths)
+                    .map(|(column, width)| {
+                        align_cell(&format_cell(song, column, None), *width, column.align())
+                    })
+                    .collect();
+                let row = row.join(" ");
+                ListItem::new(row)
+            }
+        })
+        .collect();
+    let items = List::new(items)
+        .block(Block::default().borders(Borders::TOP))
+        .style(config.styles().normal())
+        .highlight_style(config.styles().selected());
+    f.render_stateful_widget(items, chunks[1], browser.state_mut());
+}
+// }}}
+
+// {{{ 3rd tab
+fn draw_tab_three<B>(f: &mut Frame<B>, app: &mut App, area: Rect, config: &Config, _mpd: &Mpd)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let library = app.library_mut();
+    let columns: [(&str, Vec<ListItem>); 3] = [
+        (
+            library.tag().name(),
+            library
+                .tags()
+                .iter()
+                .map(|tag| ListItem::new(tag.clone()))
+                .collect(),
+        ),
+        (
+            "Album",
+            library
+                .albums()
+                .iter()
+                .map(|album| ListItem::new(album.clone()))
+                .collect(),
+        ),
+        (
+            "Track",
+            library
+                .tracks()
+                .iter()
+                .map(|song| {
+                    let title = song.title.as_ref().unwrap_or(&song.file);
+                    match song_tag(song, "Track") {
+                        Some(track) => ListItem::new(format!("{}. {}", track, title)),
+                        None => ListItem::new(title.clone()),
+                    }
+                })
+                .collect(),
+        ),
+    ];
+
+    let active = library.column();
+    for (i, ((title, items), area)) in columns.into_iter().zip(chunks).enumerate() {
+        let border_style = if i == active {
+            config.styles().tab_selected()
+        } else {
+            Style::default()
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(border_style),
+            )
+            .style(config.styles().normal())
+            .highlight_style(config.styles().selected());
+        f.render_stateful_widget(list, area, library.state_mut(i));
+    }
+}
+// }}}
+
+// {{{ 4th tab
+fn draw_tab_four<B>(f: &mut Frame<B>, app: &mut App, area: Rect, config: &Config, mpd: &Mpd)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .split(area);
+
+    let playlists = app.playlists_mut();
+    let names: Vec<ListItem> = mpd
+        .playlists()
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .map(|playlist| ListItem::new(playlist.name.clone()))
+        .collect();
+    let songs: Vec<ListItem> = playlists
+        .songs()
+        .iter()
+        .map(|song| {
+            let title = song.title.as_ref().unwrap_or(&song.file);
+            match &song.artist {
+                Some(artist) => ListItem::new(format!("{} - {}", artist, title)),
+                None => ListItem::new(title.clone()),
+            }
+        })
+        .collect();
+
+    let active = playlists.column();
+    for (i, ((title, items), area)) in [("Playlists", names), ("Songs", songs)]
+        .into_iter()
+        .zip(chunks)
+        .enumerate()
+    {
+        let border_style = if i == active {
+            config.styles().tab_selected()
+        } else {
+            Style::default()
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(border_style),
+            )
+            .style(config.styles().normal())
+            .highlight_style(config.styles().selected());
+        f.render_stateful_widget(list, area, playlists.state_mut(i));
+    }
+}
+// }}}
+
+// {{{ 5th tab
+fn draw_tab_five<B>(f: &mut Frame<B>, app: &mut App, area: Rect, config: &Config, _mpd: &Mpd)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let search = app.search_mut();
+    let label = format!(
+        "{} ({}): ",
+        search.scope().name(),
+        if search.exact() { "exact" } else { "contains" }
+    );
+    let input_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(label.len() as u16), Constraint::Min(1)].as_ref())
+        .split(chunks[0]);
+    let label = Paragraph::new(label).style(config.styles().normal().fg(Color::Cyan));
+    f.render_widget(label, input_chunks[0]);
+    f.render_widget(search.input(), input_chunks[1]);
+    if search.editing() {
+        let (x, y) = search.input().cursor_position(input_chunks[1]);
+        f.set_cursor(x, y);
     }
+
+    let version = search.version();
+    let (results, state) = search.results_mut();
+    let results =
+        Queue::new(results, version, config).block(Block::default().borders(Borders::TOP));
+    f.render_stateful_widget(results, chunks[1], state);
 }
 // }}}
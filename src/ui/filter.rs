@@ -0,0 +1,145 @@
+//! Incremental search over the queue, either jumping between the matches or showing only them
+
+use crate::{
+    config::Column,
+    ui::{queue::format_cell, text_input::TextInput},
+};
+use mpd::Song;
+
+#[derive(Debug)]
+/// Pattern typed in after `/` and the positions of the songs matching it
+pub struct QueueFilter {
+    /// Columns of the queue, the pattern is matched against what they show
+    layout: Vec<Column>,
+    input: TextInput,
+    /// Whether keys go to the input
+    editing: bool,
+    /// Whether only the matching songs are shown
+    filtering: bool,
+    /// Lowercased pattern the matches were found for
+    pattern: String,
+    /// Positions of the matching songs in the queue, ascending
+    matches: Vec<usize>,
+    /// Cursor before the pattern was being typed, to go back to when cancelled
+    origin: Option<usize>,
+}
+
+impl QueueFilter {
+    pub(crate) fn new(layout: &[Column]) -> Self {
+        Self {
+            layout: layout.to_vec(),
+            input: TextInput::default(),
+            editing: false,
+            filtering: false,
+            pattern: String::new(),
+            matches: Vec::new(),
+            origin: None,
+        }
+    }
+
+    /// Starts typing a new pattern
+    pub(crate) fn start(&mut self, cursor: Option<usize>) {
+        self.input = TextInput::default();
+        self.editing = true;
+        self.origin = cursor;
+    }
+
+    /// Stops typing, keeping the pattern for jumping between the matches
+    pub(crate) fn accept(&mut self) {
+        self.editing = false;
+    }
+
+    /// Stops typing and drops the pattern, returns where the cursor was before
+    pub(crate) fn cancel(&mut self) -> Option<usize> {
+        self.editing = false;
+        self.input = TextInput::default();
+        self.pattern.clear();
+        self.matches.clear();
+        self.origin.take()
+    }
+
+    pub(crate) fn toggle_filtering(&mut self) {
+        self.filtering = !self.filtering;
+    }
+
+    /// Finds the songs matching the pattern in any of the columns
+    pub(crate) fn update(&mut self, songs: &[Song]) {
+        self.pattern = self.input.value().to_lowercase();
+        self.matches.clear();
+        if self.pattern.is_empty() {
+            return;
+        }
+
+        self.matches = songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| {
+                self.layout.iter().any(|column| {
+                    format_cell(song, column, None)
+                        .to_lowercase()
+                        .contains(&self.pattern)
+                })
+            })
+            .map(|(pos, _)| pos)
+            .collect();
+    }
+
+    /// First match after `pos`, or before it if `forward` is false, wrapping around
+    pub(crate) fn next_match(&self, pos: Option<usize>, forward: bool) -> Option<usize> {
+        let (first, last) = (*self.matches.first()?, *self.matches.last()?);
+        let Some(pos) = pos else {
+            return Some(first);
+        };
+
+        if forward {
+            let i = self.matches.partition_point(|m| *m <= pos);
+            Some(self.matches.get(i).copied().unwrap_or(first))
+        } else {
+            let i = self.matches.partition_point(|m| *m < pos);
+            Some(i.checked_sub(1).map_or(last, |i| self.matches[i]))
+        }
+    }
+
+    /// First match at `pos` or after it, wrapping around
+    pub(crate) fn match_from(&self, pos: Option<usize>) -> Option<usize> {
+        match pos {
+            Some(pos) => self.next_match(pos.checked_sub(1), true),
+            None => self.next_match(None, true),
+        }
+    }
+
+    /// Cursor before the pattern was being typed
+    pub(crate) const fn origin(&self) -> Option<usize> {
+        self.origin
+    }
+
+    pub(crate) fn input_mut(&mut self) -> &mut TextInput {
+        &mut self.input
+    }
+
+    pub(crate) const fn input(&self) -> &TextInput {
+        &self.input
+    }
+
+    pub(crate) const fn editing(&self) -> bool {
+        self.editing
+    }
+
+    /// Whether there is a pattern to match
+    pub(crate) fn active(&self) -> bool {
+        !self.pattern.is_empty()
+    }
+
+    pub(crate) fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub(crate) fn matches(&self) -> &[usize] {
+        &self.matches
+    }
+
+    /// Positions of the rows to show if only the matches are, otherwise `None`
+    pub(crate) fn rows(&self) -> Option<&[usize]> {
+        (self.filtering && self.active()).then_some(self.matches.as_slice())
+    }
+}
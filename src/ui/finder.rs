@@ -0,0 +1,430 @@
+//! Fuzzy finder over every song of the library.
+//! The songs are fetched and scored on a thread of their own, so typing stays responsive on big libraries
+
+use crate::{
+    config::Connection,
+    events::Message,
+    mpd::{connect, list_all_info, song_tag, AddMode, Mpd},
+    ui::text_input::TextInput,
+};
+use mpd::Song;
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+use tui::widgets::ListState;
+
+/// How many of the best matches are shown
+const MAX_RESULTS: usize = 200;
+/// How many songs are scored between checks for a newer pattern
+const CHUNK_SIZE: usize = 4096;
+
+/// Pattern to rank the songs by
+struct Request {
+    generation: u64,
+    /// Lowercased
+    pattern: String,
+    /// Whether the library has to be fetched again first
+    reload: bool,
+}
+
+/// Best matches for the pattern of the request with the same generation
+struct Response {
+    generation: u64,
+    songs: Vec<Song>,
+    matched: usize,
+    total: usize,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Worker {
+    tx: Sender<Request>,
+    rx: Receiver<Response>,
+}
+
+#[derive(Debug)]
+/// Popup ranking the songs of the library by how well they match the pattern typed in
+pub struct Finder {
+    connection: Connection,
+    /// Spawned when the finder is opened for the first time
+    worker: Option<Worker>,
+    open: bool,
+    input: TextInput,
+    /// Generation of the last pattern sent, older results are dropped
+    generation: u64,
+    results: Vec<Song>,
+    matched: usize,
+    /// Is `None` until the library is fetched
+    total: Option<usize>,
+    error: Option<String>,
+    /// Whether the library changed since it was fetched
+    stale: bool,
+    state: ListState,
+    /// Told when results arrive
+    notify: Option<Sender<Message>>,
+}
+
+impl Finder {
+    pub(crate) fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            worker: None,
+            open: false,
+            input: TextInput::default(),
+            generation: 0,
+            results: Vec::new(),
+            matched: 0,
+            total: None,
+            error: None,
+            stale: false,
+            state: ListState::default(),
+            notify: None,
+        }
+    }
+
+    /// Sends `Message::Finder` over the channel whenever results arrive, so they are polled
+    pub(crate) fn notify(&mut self, tx: Sender<Message>) {
+        self.notify = Some(tx);
+    }
+
+    pub(crate) fn open(&mut self) {
+        self.open = true;
+        self.input = TextInput::default();
+        self.find();
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Ranks the songs by the pattern typed in, the results arrive through `poll`
+    pub(crate) fn find(&mut self) {
+        let (connection, notify) = (&self.connection, &self.notify);
+        let worker = self
+            .worker
+            .get_or_insert_with(|| spawn(connection.clone(), notify.clone()));
+        self.generation += 1;
+        let request = Request {
+            generation: self.generation,
+            pattern: self.input.value().to_lowercase(),
+            reload: std::mem::take(&mut self.stale),
+        };
+        if worker.tx.send(request).is_err() {
+            // the thread is gone, start over the next time
+            self.worker = None;
+        }
+    }
+
+    /// Fetches the library again before the next search, since the database changed
+    pub(crate) fn invalidate(&mut self) {
+        self.stale = true;
+        if self.open {
+            self.find();
+        }
+    }
+
+    /// Takes the results of the last pattern if they arrived, returns whether they did
+    pub(crate) fn poll(&mut self) -> bool {
+        let Some(worker) = &self.worker else {
+            return false;
+        };
+        let Some(response) = worker
+            .rx
+            .try_iter()
+            .filter(|response| response.generation == self.generation)
+            .last()
+        else {
+            return false;
+        };
+
+        self.results = response.songs;
+        self.matched = response.matched;
+        self.total = Some(response.total);
+        self.error = response.error;
+        self.state.select((!self.results.is_empty()).then_some(0));
+        true
+    }
+
+    pub(crate) fn next(&mut self) {
+        let len = self.results.len();
+        if len == 0 {
+            return;
+        }
+        let i = self.state.selected().map_or(0, |i| (i + 1) % len);
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let len = self.results.len();
+        if len == 0 {
+            return;
+        }
+        let i = self
+            .state
+            .selected()
+            .map_or(0, |i| i.checked_sub(1).unwrap_or(len - 1));
+        self.state.select(Some(i));
+    }
+
+    /// Adds the selected song to the queue and closes the finder
+    pub(crate) fn pick(&mut self, mpd: &mut Mpd, mode: AddMode) {
+        let Some(pos) = self
+            .state
+            .selected()
+            .filter(|pos| *pos < self.results.len())
+        else {
+            return;
+        };
+        mpd.add_songs(&self.results[pos..=pos], mode);
+        self.close();
+    }
+
+    pub(crate) const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub(crate) const fn input(&self) -> &TextInput {
+        &self.input
+    }
+
+    pub(crate) fn input_mut(&mut self) -> &mut TextInput {
+        &mut self.input
+    }
+
+    pub(crate) const fn matched(&self) -> usize {
+        self.matched
+    }
+
+    pub(crate) const fn total(&self) -> Option<usize> {
+        self.total
+    }
+
+    pub(crate) fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Results along with the selection in them, for rendering
+    pub(crate) fn results_mut(&mut self) -> (&[Song], &mut ListState) {
+        (&self.results, &mut self.state)
+    }
+}
+
+/// Text the pattern is matched against
+fn candidate(song: &Song) -> String {
+    let artist = song.artist.as_deref().unwrap_or_default();
+    let album = song_tag(song, "Album").unwrap_or_default();
+    let title = song.title.as_deref().unwrap_or(&song.file);
+    format!("{} {} {}", artist, album, title).to_lowercase()
+}
+
+/// Spawns the thread which fetches the library on its own connection and ranks it
+fn spawn(connection: Connection, notify: Option<Sender<Message>>) -> Worker {
+    let (tx, requests) = mpsc::channel::<Request>();
+    let (responses, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut songs: Vec<Song> = Vec::new();
+        let mut candidates: Vec<String> = Vec::new();
+        let mut loaded = false;
+        let mut next = None;
+        loop {
+            let Some(request) = latest(&requests, next.take().or_else(|| requests.recv().ok()))
+            else {
+                return;
+            };
+
+            let mut error = None;
+            if request.reload || !loaded {
+                match connect(&connection).and_then(|mut client| list_all_info(&mut client)) {
+                    Ok(library) => {
+                        candidates = library.iter().map(candidate).collect();
+                        songs = library;
+                        loaded = true;
+                    }
+                    Err(e) => error = Some(e.to_string()),
+                }
+            }
+
+            let mut newer = None;
+            let ranked = rank(&candidates, &request.pattern, || {
+                newer = latest(&requests, newer.take());
+                newer.is_some()
+            });
+            let Some((best, matched)) = ranked else {
+                // typed on meanwhile, the newer pattern is ranked instead
+                next = newer;
+                continue;
+            };
+
+            let response = Response {
+                generation: request.generation,
+                songs: best.into_iter().map(|i| songs[i].clone()).collect(),
+                matched,
+                total: songs.len(),
+                error,
+            };
+            if responses.send(response).is_err() {
+                return;
+            }
+            if let Some(notify) = &notify {
+                notify.send(Message::Finder).ok();
+            }
+        }
+    });
+
+    Worker { tx, rx }
+}
+
+/// Skips to the last of the queued requests, keeping whether any of them asked for a reload
+fn latest(rx: &Receiver<Request>, first: Option<Request>) -> Option<Request> {
+    rx.try_iter().fold(first, |last, request| {
+        let reload = request.reload || last.is_some_and(|last| last.reload);
+        Some(Request { reload, ..request })
+    })
+}
+
+/// Indices of the best matching candidates along with how many matched at all.
+/// Returns `None` as soon as `interrupted` does
+fn rank(
+    candidates: &[String],
+    pattern: &str,
+    mut interrupted: impl FnMut() -> bool,
+) -> Option<(Vec<usize>, usize)> {
+    let words: Vec<Vec<char>> = pattern
+        .split_whitespace()
+        .map(|word| word.chars().collect())
+        .collect();
+    if words.is_empty() {
+        let best = (0..candidates.len().min(MAX_RESULTS)).collect();
+        return Some((best, candidates.len()));
+    }
+
+    let mut scored: Vec<(u32, usize)> = Vec::new();
+    for (chunk, candidates) in candidates.chunks(CHUNK_SIZE).enumerate() {
+        if interrupted() {
+            return None;
+        }
+        let offset = chunk * CHUNK_SIZE;
+        scored.extend(candidates.iter().enumerate().filter_map(|(i, candidate)| {
+            let score = words
+                .iter()
+                .map(|word| score(candidate, word))
+                .sum::<Option<u32>>()?;
+            Some((score, offset + i))
+        }));
+    }
+
+    let matched = scored.len();
+    // higher scores first, then shorter candidates
+    let order = |a: &(u32, usize), b: &(u32, usize)| {
+        b.0.cmp(&a.0)
+            .then_with(|| candidates[a.1].len().cmp(&candidates[b.1].len()))
+    };
+    if scored.len() > MAX_RESULTS {
+        scored.select_nth_unstable_by(MAX_RESULTS, order);
+        scored.truncate(MAX_RESULTS);
+    }
+    scored.sort_unstable_by(order);
+
+    Some((scored.into_iter().map(|(_, i)| i).collect(), matched))
+}
+
+/// How well the chars of the word appear in the candidate in order, `None` if they do not.
+/// Consecutive chars and ones starting a word score higher
+fn score(candidate: &str, word: &[char]) -> Option<u32> {
+    let mut chars = word.iter().peekable();
+    let mut score = 0;
+    let mut prev = ' ';
+    let mut prev_matched = false;
+    for c in candidate.chars() {
+        let Some(wanted) = chars.peek() else {
+            break;
+        };
+        if c == **wanted {
+            chars.next();
+            score += 1;
+            if prev_matched {
+                score += 4;
+            }
+            if !prev.is_alphanumeric() {
+                score += 3;
+            }
+            prev_matched = true;
+        } else {
+            prev_matched = false;
+        }
+        prev = c;
+    }
+
+    chars.peek().is_none().then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(word: &str) -> Vec<char> {
+        word.chars().collect()
+    }
+
+    fn candidates(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn score_needs_every_char_in_order() {
+        assert_eq!(score("abc", &chars("cb")), None);
+        assert_eq!(score("abc", &chars("abcd")), None);
+        assert_eq!(score("abc", &chars("")), Some(0));
+    }
+
+    #[test]
+    fn score_favours_consecutive_chars_and_word_starts() {
+        // a: 1 + 3 for the start, b and c: 1 + 4 each for following a match
+        assert_eq!(score("abc", &chars("abc")), Some(14));
+        // the same chars spread out only get the start bonus on `a`
+        assert_eq!(score("axbxc", &chars("abc")), Some(6));
+        // unless they start words too
+        assert_eq!(score("a_b_c", &chars("abc")), Some(12));
+        // `b` starts a word but follows no match
+        assert_eq!(score("xa b", &chars("ab")), Some(5));
+    }
+
+    #[test]
+    fn rank_orders_by_score_then_length() {
+        let texts = candidates(&["the sun", "sunset boulevard", "sun", "moon", "as unknown"]);
+        let (best, matched) = rank(&texts, "sun", || false).unwrap();
+        assert_eq!(matched, 4);
+        assert_eq!(best, [2, 0, 1, 4]);
+    }
+
+    #[test]
+    fn rank_needs_every_word() {
+        let texts = candidates(&["artist album title", "artist other"]);
+        let (best, matched) = rank(&texts, "art title", || false).unwrap();
+        assert_eq!((best, matched), (vec![0], 1));
+    }
+
+    #[test]
+    fn rank_without_words_keeps_the_library_order() {
+        let texts = candidates(&["b", "a"]);
+        assert_eq!(rank(&texts, "  ", || false), Some((vec![0, 1], 2)));
+    }
+
+    #[test]
+    fn rank_keeps_only_the_best_results() {
+        let texts: Vec<String> = (0..MAX_RESULTS + 50)
+            .map(|i| format!("song {}", "x".repeat(i)))
+            .collect();
+        let (best, matched) = rank(&texts, "song", || false).unwrap();
+        assert_eq!(matched, MAX_RESULTS + 50);
+        assert_eq!(best, (0..MAX_RESULTS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rank_stops_when_interrupted() {
+        let texts = candidates(&["a"]);
+        assert_eq!(rank(&texts, "a", || true), None);
+    }
+}
@@ -0,0 +1,308 @@
+//! Undo and redo of the changes made to the queue
+
+use crate::mpd::{quote, Mpd};
+use mpd::{Id, Subsystem};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Songs of the queue in order, as their ids along with their files
+pub type Snapshot = Vec<(Id, String)>;
+
+#[derive(Debug)]
+/// Queue before and after a change
+struct Change {
+    before: Vec<Id>,
+    after: Vec<Id>,
+    /// Files of the songs only one side has, to add them back
+    files: HashMap<u32, String>,
+}
+
+#[derive(Debug)]
+pub struct History {
+    /// Latest last
+    undo: VecDeque<Change>,
+    redo: Vec<Change>,
+    /// How many changes are kept
+    depth: usize,
+}
+
+impl History {
+    pub(crate) fn new(depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            depth,
+        }
+    }
+
+    pub(crate) fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.undo.len() > depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// The queue as MPD has it now, caught up first with changes idle has not told of yet
+    pub(crate) fn snapshot(mpd: &mut Mpd) -> Option<Snapshot> {
+        mpd.update(&[Subsystem::Queue]);
+        queue_snapshot(mpd)
+    }
+
+    /// Keeps the change from `before` to the queue as it is now, if there is any
+    pub(crate) fn record(&mut self, mpd: &Mpd, before: Snapshot) {
+        if let Some(after) = queue_snapshot(mpd) {
+            self.push(before, after);
+        }
+    }
+
+    fn push(&mut self, before: Snapshot, after: Snapshot) {
+        let before_ids: HashSet<u32> = before.iter().map(|(id, _)| id.0).collect();
+        let after_ids: HashSet<u32> = after.iter().map(|(id, _)| id.0).collect();
+        let same =
+            before.len() == after.len() && before.iter().zip(&after).all(|((a, _), (b, _))| a == b);
+        if same || self.depth == 0 {
+            return;
+        }
+
+        let files = before
+            .iter()
+            .filter(|(id, _)| !after_ids.contains(&id.0))
+            .chain(after.iter().filter(|(id, _)| !before_ids.contains(&id.0)))
+            .map(|(id, file)| (id.0, file.clone()))
+            .collect();
+        self.undo.push_back(Change {
+            before: before.into_iter().map(|(id, _)| id).collect(),
+            after: after.into_iter().map(|(id, _)| id).collect(),
+            files,
+        });
+        if self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    /// Brings the queue back to how it was before the latest change
+    pub(crate) fn undo(&mut self, mpd: &mut Mpd) -> Result<(), String> {
+        let change = self.undo.pop_back().ok_or("nothing to undo")?;
+        let result = restore(mpd, &change.after, &change.before, &change.files);
+        self.redo.push(change);
+        self.catch_up(result)
+    }
+
+    /// Makes the latest change undone again
+    pub(crate) fn redo(&mut self, mpd: &mut Mpd) -> Result<(), String> {
+        let change = self.redo.pop().ok_or("nothing to redo")?;
+        let result = restore(mpd, &change.before, &change.after, &change.files);
+        self.undo.push_back(change);
+        self.catch_up(result)
+    }
+
+    /// Gives the songs added back their new ids everywhere, or drops the changes if restoring failed
+    fn catch_up(&mut self, result: Result<HashMap<u32, Id>, String>) -> Result<(), String> {
+        let new_ids = result.inspect_err(|_| self.clear())?;
+        let renew = |ids: &mut Vec<Id>| {
+            for id in ids {
+                *id = new_ids.get(&id.0).copied().unwrap_or(*id);
+            }
+        };
+        for change in self.undo.iter_mut().chain(&mut self.redo) {
+            renew(&mut change.before);
+            renew(&mut change.after);
+            change.files = change
+                .files
+                .drain()
+                .map(|(id, file)| (new_ids.get(&id).map_or(id, |id| id.0), file))
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// Drops the changes, they can only be undone in order
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+fn queue_snapshot(mpd: &Mpd) -> Option<Snapshot> {
+    let queue = mpd.queue()?;
+    Some(
+        queue
+            .iter()
+            .filter_map(|song| Some((song.place?.id, song.file.clone())))
+            .collect(),
+    )
+}
+
+/// Turns the queue from `from` into `to` by deleting, adding and moving its songs.
+/// Returns the new ids of the songs added back
+fn restore(
+    mpd: &mut Mpd,
+    from: &[Id],
+    to: &[Id],
+    files: &HashMap<u32, String>,
+) -> Result<HashMap<u32, Id>, String> {
+    let changed = "the queue was changed by another client, the undo history is dropped";
+    let failed = |mpd: &Mpd| {
+        mpd.error()
+            .unwrap_or("lost the connection to MPD")
+            .to_string()
+    };
+    mpd.update(&[Subsystem::Queue]);
+    let current = mpd.queue().ok_or_else(|| failed(mpd))?;
+    if current.len() != from.len()
+        || current
+            .iter()
+            .zip(from)
+            .any(|(song, id)| song.place.map(|place| place.id) != Some(*id))
+    {
+        return Err(changed.to_string());
+    }
+
+    let (commands, added) = edits(from, to, files).ok_or(changed)?;
+    let mut new_ids = HashMap::new();
+    if !commands.is_empty() {
+        let reply = mpd.command_list(&commands).ok_or_else(|| failed(mpd))?;
+        let ids = reply
+            .iter()
+            .filter(|(key, _)| key == "Id")
+            .filter_map(|(_, id)| id.parse().ok().map(Id));
+        new_ids.extend(added.iter().map(|id| id.0).zip(ids));
+        mpd.update(&[Subsystem::Queue]);
+    }
+    // the songs left and added back are all there, only their order is left
+    let positions: HashMap<u32, usize> = mpd
+        .queue()
+        .ok_or_else(|| failed(mpd))?
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, song)| Some((song.place?.id.0, pos)))
+        .collect();
+    if positions.len() != to.len() {
+        return Err(changed.to_string());
+    }
+    let order = to
+        .iter()
+        .map(|id| new_ids.get(&id.0).unwrap_or(id))
+        .map(|id| positions.get(&id.0).copied())
+        .collect::<Option<Vec<usize>>>()
+        .ok_or(changed)?;
+    mpd.reorder_queue(&order);
+    mpd.update(&[Subsystem::Queue]);
+    Ok(new_ids)
+}
+
+/// Commands deleting the songs `to` has not and adding back those `from` has not,
+/// along with the ids these had. `None` if the file of one of them is not known
+fn edits(from: &[Id], to: &[Id], files: &HashMap<u32, String>) -> Option<(Vec<String>, Vec<Id>)> {
+    let from_ids: HashSet<u32> = from.iter().map(|id| id.0).collect();
+    let to_ids: HashSet<u32> = to.iter().map(|id| id.0).collect();
+    let added: Vec<Id> = to
+        .iter()
+        .filter(|id| !from_ids.contains(&id.0))
+        .copied()
+        .collect();
+    let mut commands: Vec<String> = from
+        .iter()
+        .filter(|id| !to_ids.contains(&id.0))
+        .map(|id| format!("deleteid {}", id.0))
+        .collect();
+    for id in &added {
+        commands.push(format!("addid {}", quote(files.get(&id.0)?)));
+    }
+    Some((commands, added))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(ids: &[u32]) -> Snapshot {
+        ids.iter()
+            .map(|&id| (Id(id), format!("{}.flac", id)))
+            .collect()
+    }
+
+    fn ids(ids: &[u32]) -> Vec<Id> {
+        ids.iter().copied().map(Id).collect()
+    }
+
+    #[test]
+    fn records_only_changes() {
+        let mut history = History::new(10);
+        history.push(snapshot(&[1, 2]), snapshot(&[1, 2]));
+        assert!(history.undo.is_empty());
+
+        history.push(snapshot(&[1, 2]), snapshot(&[2, 1]));
+        let change = &history.undo[0];
+        assert_eq!(change.before, ids(&[1, 2]));
+        assert_eq!(change.after, ids(&[2, 1]));
+        assert!(change.files.is_empty());
+
+        let mut history = History::new(0);
+        history.push(snapshot(&[1]), snapshot(&[]));
+        assert!(history.undo.is_empty());
+    }
+
+    #[test]
+    fn records_the_files_of_songs_only_one_side_has() {
+        let mut history = History::new(10);
+        history.push(snapshot(&[1, 2]), snapshot(&[2, 3]));
+        let files = &history.undo[0].files;
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[&1], "1.flac");
+        assert_eq!(files[&3], "3.flac");
+    }
+
+    #[test]
+    fn recording_forgets_the_oldest_and_the_redone_changes() {
+        let mut history = History::new(2);
+        history.push(snapshot(&[]), snapshot(&[1]));
+        history.push(snapshot(&[1]), snapshot(&[1, 2]));
+        history.redo.push(history.undo.pop_back().unwrap());
+        history.push(snapshot(&[1]), snapshot(&[]));
+        history.push(snapshot(&[]), snapshot(&[3]));
+        assert!(history.redo.is_empty());
+        assert_eq!(history.undo.len(), 2);
+        assert_eq!(history.undo[0].after, ids(&[]));
+        assert_eq!(history.undo[1].after, ids(&[3]));
+    }
+
+    #[test]
+    fn restoring_deletes_and_adds_back_songs() {
+        let files = HashMap::from([(3, "a b.flac".to_string())]);
+        let (commands, added) = edits(&ids(&[1, 2]), &ids(&[3, 1]), &files).unwrap();
+        assert_eq!(commands, ["deleteid 2", "addid \"a b.flac\""]);
+        assert_eq!(added, ids(&[3]));
+
+        assert_eq!(
+            edits(&ids(&[1, 2]), &ids(&[2, 1]), &files),
+            Some((Vec::new(), Vec::new()))
+        );
+        // the file of a song added back is needed
+        assert_eq!(edits(&ids(&[1]), &ids(&[1, 4]), &files), None);
+    }
+
+    #[test]
+    fn catching_up_renews_the_ids_of_songs_added_back() {
+        let mut history = History::new(10);
+        history.push(snapshot(&[1, 2]), snapshot(&[1]));
+        history.push(snapshot(&[1]), snapshot(&[1, 3]));
+        history.redo.push(history.undo.pop_back().unwrap());
+
+        // undoing the first change added song 2 back as 7
+        history.catch_up(Ok(HashMap::from([(2, Id(7))]))).unwrap();
+        assert_eq!(history.undo[0].before, ids(&[1, 7]));
+        assert_eq!(history.undo[0].files[&7], "2.flac");
+        assert!(!history.undo[0].files.contains_key(&2));
+        assert_eq!(history.redo[0].after, ids(&[1, 3]));
+    }
+
+    #[test]
+    fn failing_to_restore_drops_the_history() {
+        let mut history = History::new(10);
+        history.push(snapshot(&[1]), snapshot(&[]));
+        history.redo.push(history.undo.pop_back().unwrap());
+        assert!(history.catch_up(Err("failed".to_string())).is_err());
+        assert!(history.undo.is_empty() && history.redo.is_empty());
+    }
+}
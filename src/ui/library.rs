@@ -0,0 +1,238 @@
+//! Library of the songs grouped by tags: tag → album → track
+
+use crate::{
+    config::{self, AlbumSort, LibraryTag},
+    mpd::{song_tag, AddMode, Mpd},
+};
+use mpd::Song;
+use std::collections::HashMap;
+use tui::widgets::ListState;
+
+const TAGS: usize = 0;
+const ALBUMS: usize = 1;
+const TRACKS: usize = 2;
+
+#[derive(Debug)]
+/// Lists of the three columns and what is selected in them
+pub struct Library {
+    tag: LibraryTag,
+    sort_albums: AlbumSort,
+    /// Column being navigated
+    column: usize,
+    tags: Vec<String>,
+    albums: Vec<String>,
+    tracks: Vec<Song>,
+    states: [ListState; 3],
+    loaded: bool,
+}
+
+impl Library {
+    pub(crate) fn new(config: &config::Library) -> Self {
+        Self {
+            tag: config.tag(),
+            sort_albums: config.sort_albums(),
+            column: TAGS,
+            tags: Vec::new(),
+            albums: Vec::new(),
+            tracks: Vec::new(),
+            states: Default::default(),
+            loaded: false,
+        }
+    }
+
+    /// Lists the tags if it was not done yet
+    pub(crate) fn load(&mut self, mpd: &mut Mpd) {
+        if !self.loaded {
+            self.reload_tags(mpd);
+        }
+    }
+
+    /// Lists everything again since the database was changed, if it was listed before
+    pub(crate) fn invalidate(&mut self, mpd: &mut Mpd) {
+        if self.loaded {
+            self.reload_tags(mpd);
+        }
+    }
+
+    fn reload_tags(&mut self, mpd: &mut Mpd) {
+        let Some(tags) = mpd.list_tag(self.tag.name(), &[]) else {
+            return;
+        };
+        self.tags = tags;
+        self.loaded = true;
+        Self::clamp(&mut self.states[TAGS], self.tags.len());
+        self.reload_albums(mpd);
+    }
+
+    fn reload_albums(&mut self, mpd: &mut Mpd) {
+        self.albums.clear();
+        if let Some(tag) = self.selected_tag().map(str::to_string) {
+            let filter = [(self.tag.name(), tag.as_str())];
+            self.albums = mpd.list_tag("Album", &filter).unwrap_or_default();
+
+            if self.sort_albums == AlbumSort::Date {
+                let mut dates: HashMap<&str, &str> = HashMap::new();
+                let songs = mpd.find_songs(&filter).unwrap_or_default();
+                for song in &songs {
+                    let (Some(album), Some(date)) =
+                        (song_tag(song, "Album"), song_tag(song, "Date"))
+                    else {
+                        continue;
+                    };
+                    let earliest = dates.entry(album).or_insert(date);
+                    *earliest = (*earliest).min(date);
+                }
+                self.albums.sort_by_cached_key(|album| {
+                    (
+                        dates.get(album.as_str()).map(|date| date.to_string()),
+                        album.clone(),
+                    )
+                });
+            }
+        }
+        Self::clamp(&mut self.states[ALBUMS], self.albums.len());
+        self.reload_tracks(mpd);
+    }
+
+    fn reload_tracks(&mut self, mpd: &mut Mpd) {
+        let filter = self
+            .selected_tag()
+            .zip(self.selected_album())
+            .map(|(tag, album)| [(self.tag.name(), tag), ("Album", album)]);
+        let mut tracks = filter
+            .and_then(|filter| mpd.find_songs(&filter))
+            .unwrap_or_default();
+        tracks.sort_by_key(|song| (tag_number(song, "Disc"), tag_number(song, "Track")));
+        self.tracks = tracks;
+        Self::clamp(&mut self.states[TRACKS], self.tracks.len());
+    }
+
+    /// Keeps the selection inside the list of `len` items
+    fn clamp(state: &mut ListState, len: usize) {
+        let selected = state.selected().unwrap_or(0);
+        state.select((len > 0).then(|| selected.min(len - 1)));
+    }
+
+    fn selected_tag(&self) -> Option<&str> {
+        self.tags
+            .get(self.states[TAGS].selected()?)
+            .map(String::as_str)
+    }
+
+    fn selected_album(&self) -> Option<&str> {
+        self.albums
+            .get(self.states[ALBUMS].selected()?)
+            .map(String::as_str)
+    }
+
+    fn len(&self, column: usize) -> usize {
+        match column {
+            TAGS => self.tags.len(),
+            ALBUMS => self.albums.len(),
+            _ => self.tracks.len(),
+        }
+    }
+
+    /// Moves the selection of the current column by `delta` wrapping around,
+    /// relisting the columns to the right of it
+    fn move_selection(&mut self, mpd: &mut Mpd, delta: isize) {
+        let len = self.len(self.column);
+        if len == 0 {
+            return;
+        }
+        let state = &mut self.states[self.column];
+        let selected = state.selected().map_or(0, |i| i as isize + delta);
+        state.select(Some(selected.rem_euclid(len as isize) as usize));
+
+        match self.column {
+            TAGS => {
+                self.states[ALBUMS].select(Some(0));
+                self.states[TRACKS].select(Some(0));
+                self.reload_albums(mpd);
+            }
+            ALBUMS => {
+                self.states[TRACKS].select(Some(0));
+                self.reload_tracks(mpd);
+            }
+            _ => (),
+        }
+    }
+
+    pub(crate) fn next(&mut self, mpd: &mut Mpd) {
+        self.move_selection(mpd, 1);
+    }
+
+    pub(crate) fn previous(&mut self, mpd: &mut Mpd) {
+        self.move_selection(mpd, -1);
+    }
+
+    /// Moves to the column on the right if it has anything in it
+    pub(crate) fn column_right(&mut self) {
+        if self.column < TRACKS && self.len(self.column + 1) > 0 {
+            self.column += 1;
+        }
+    }
+
+    pub(crate) fn column_left(&mut self) {
+        self.column = self.column.saturating_sub(1);
+    }
+
+    /// Adds the songs under the selection of the current column to the queue
+    pub(crate) fn add(&self, mpd: &mut Mpd, mode: AddMode) {
+        let songs = match self.column {
+            TAGS => {
+                let Some(tag) = self.selected_tag() else {
+                    return;
+                };
+                let Some(songs) = mpd.find_songs(&[(self.tag.name(), tag)]) else {
+                    return;
+                };
+                songs
+            }
+            ALBUMS => self.tracks.clone(),
+            _ => {
+                let selected = self.states[TRACKS].selected();
+                selected
+                    .and_then(|i| self.tracks.get(i))
+                    .cloned()
+                    .into_iter()
+                    .collect()
+            }
+        };
+        mpd.add_songs(&songs, mode);
+    }
+
+    pub(crate) const fn tag(&self) -> LibraryTag {
+        self.tag
+    }
+
+    pub(crate) const fn column(&self) -> usize {
+        self.column
+    }
+
+    pub(crate) fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub(crate) fn albums(&self) -> &[String] {
+        &self.albums
+    }
+
+    pub(crate) fn tracks(&self) -> &[Song] {
+        &self.tracks
+    }
+
+    pub(crate) fn state_mut(&mut self, column: usize) -> &mut ListState {
+        &mut self.states[column]
+    }
+}
+
+/// Number at the start of a tag like `Track: 3/12`, songs without it go last
+fn tag_number(song: &Song, tag: &str) -> u32 {
+    song_tag(song, tag)
+        .and_then(|value| {
+            let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .unwrap_or(u32::MAX)
+}
@@ -1,5 +1,16 @@
 pub mod app;
+pub mod browser;
+pub mod command_line;
 pub mod draw;
+pub mod filter;
+pub mod finder;
+pub mod history;
+pub mod library;
+pub mod playlists;
+pub mod prompt;
+pub mod queue;
+pub mod search;
+pub mod text_input;
 
 /// Formats time (in seconds) to human readable {min:sec}
 ///
@@ -0,0 +1,196 @@
+//! Stored playlists and the songs of the selected one
+
+use crate::{
+    mpd::{AddMode, Mpd},
+    ui::prompt::{Prompt, PromptAction},
+};
+use mpd::Song;
+use tui::widgets::ListState;
+
+const NAMES: usize = 0;
+const SONGS: usize = 1;
+
+#[derive(Debug, Default)]
+/// Selection in the list of playlists and in the songs of the selected one
+pub struct StoredPlaylists {
+    /// Column being navigated
+    column: usize,
+    states: [ListState; 2],
+    /// Name of the selected playlist, to keep it selected when the list changes
+    selected: Option<String>,
+    /// Songs of the selected playlist
+    songs: Vec<Song>,
+}
+
+impl StoredPlaylists {
+    /// Lists the songs of the selected playlist again, keeping the selections inside the lists
+    pub(crate) fn load(&mut self, mpd: &mut Mpd) {
+        let playlists = mpd.playlists().map_or(&[][..], Vec::as_slice);
+        let pos = self.selected.as_ref().and_then(|selected| {
+            playlists
+                .iter()
+                .position(|playlist| &playlist.name == selected)
+        });
+        if pos.is_some() {
+            self.states[NAMES].select(pos);
+        }
+        Self::clamp(&mut self.states[NAMES], playlists.len());
+        self.selected = self.selected_name(mpd);
+
+        self.songs = match self.selected_name(mpd) {
+            Some(name) => mpd
+                .command(|client| client.playlist(name.as_str()))
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        Self::clamp(&mut self.states[SONGS], self.songs.len());
+        if self.songs.is_empty() {
+            self.column = NAMES;
+        }
+    }
+
+    /// Selects the playlist once it shows up in the list
+    pub(crate) fn select_name(&mut self, name: &str) {
+        self.selected = Some(name.to_string());
+    }
+
+    /// Keeps the selection inside the list of `len` items
+    fn clamp(state: &mut ListState, len: usize) {
+        let selected = state.selected().unwrap_or(0);
+        state.select((len > 0).then(|| selected.min(len - 1)));
+    }
+
+    pub(crate) fn selected_name(&self, mpd: &Mpd) -> Option<String> {
+        let playlist = mpd.playlists()?.get(self.states[NAMES].selected()?)?;
+        Some(playlist.name.clone())
+    }
+
+    fn selected_song(&self) -> Option<usize> {
+        self.states[SONGS]
+            .selected()
+            .filter(|pos| *pos < self.songs.len())
+    }
+
+    fn move_selection(&mut self, mpd: &mut Mpd, delta: isize) {
+        let len = match self.column {
+            NAMES => mpd.playlists().map_or(0, Vec::len),
+            _ => self.songs.len(),
+        };
+        if len == 0 {
+            return;
+        }
+        let state = &mut self.states[self.column];
+        let selected = state.selected().map_or(0, |i| i as isize + delta);
+        state.select(Some(selected.rem_euclid(len as isize) as usize));
+
+        if self.column == NAMES {
+            self.states[SONGS].select(Some(0));
+            self.selected = None;
+            self.load(mpd);
+        }
+    }
+
+    pub(crate) fn next(&mut self, mpd: &mut Mpd) {
+        self.move_selection(mpd, 1);
+    }
+
+    pub(crate) fn previous(&mut self, mpd: &mut Mpd) {
+        self.move_selection(mpd, -1);
+    }
+
+    /// Moves to the songs of the selected playlist
+    pub(crate) fn open(&mut self) {
+        if !self.songs.is_empty() {
+            self.column = SONGS;
+        }
+    }
+
+    /// Moves back to the list of playlists
+    pub(crate) fn back(&mut self) {
+        self.column = NAMES;
+    }
+
+    /// Adds the selected playlist or song to the queue
+    pub(crate) fn add(&self, mpd: &mut Mpd, mode: AddMode) {
+        let songs = match self.column {
+            NAMES => &self.songs[..],
+            _ => match self.selected_song() {
+                Some(pos) => &self.songs[pos..=pos],
+                None => return,
+            },
+        };
+        mpd.add_songs(songs, mode);
+    }
+
+    /// Moves the selected song inside the playlist by `delta` positions
+    pub(crate) fn move_song(&mut self, mpd: &mut Mpd, delta: isize) {
+        let Some(name) = self.selected_name(mpd) else {
+            return;
+        };
+        let Some(pos) = self.selected_song().filter(|_| self.column == SONGS) else {
+            return;
+        };
+        let Some(to) = pos
+            .checked_add_signed(delta)
+            .filter(|to| *to < self.songs.len())
+        else {
+            return;
+        };
+
+        if mpd
+            .command(|client| client.pl_shift(name.as_str(), pos as u32, to as u32))
+            .is_some()
+        {
+            self.states[SONGS].select(Some(to));
+            self.load(mpd);
+        }
+    }
+
+    /// Prompt for deleting the selected playlist or removing the selected song from it
+    pub(crate) fn delete_prompt(&self, mpd: &Mpd) -> Option<Prompt> {
+        let name = self.selected_name(mpd)?;
+        if self.column == NAMES {
+            return Some(Prompt::confirm(
+                format!("Delete playlist \"{}\"?", name),
+                PromptAction::DeletePlaylist(name),
+            ));
+        }
+
+        let pos = self.selected_song()?;
+        let song = &self.songs[pos];
+        Some(Prompt::confirm(
+            format!(
+                "Remove \"{}\" from \"{}\"?",
+                song.title.as_ref().unwrap_or(&song.file),
+                name
+            ),
+            PromptAction::RemoveSong {
+                playlist: name,
+                pos: pos as u32,
+            },
+        ))
+    }
+
+    /// Prompt for renaming the selected playlist
+    pub(crate) fn rename_prompt(&self, mpd: &Mpd) -> Option<Prompt> {
+        let name = self.selected_name(mpd)?;
+        let prompt = Prompt::text(
+            format!("Rename \"{}\" to", name),
+            &name,
+            PromptAction::RenamePlaylist(name.clone()),
+        );
+        Some(prompt)
+    }
+
+    pub(crate) const fn column(&self) -> usize {
+        self.column
+    }
+
+    pub(crate) fn songs(&self) -> &[Song] {
+        &self.songs
+    }
+
+    pub(crate) fn state_mut(&mut self, column: usize) -> &mut ListState {
+        &mut self.states[column]
+    }
+}
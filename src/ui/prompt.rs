@@ -0,0 +1,69 @@
+//! Prompts asking to confirm an action or to type in some text for it
+
+use crate::ui::text_input::TextInput;
+use mpd::Song;
+
+#[derive(Debug, Clone)]
+/// What is done once the prompt is confirmed
+pub enum PromptAction {
+    DeletePlaylist(String),
+    /// Renames the playlist to the text typed in
+    RenamePlaylist(String),
+    RemoveSong {
+        playlist: String,
+        pos: u32,
+    },
+    /// Saves the songs, or the whole queue if there are none, as a playlist named as typed in
+    SaveQueue(Option<Vec<Song>>),
+    /// Replaces the playlist which already exists with the songs or the whole queue
+    OverwritePlaylist {
+        name: String,
+        songs: Option<Vec<Song>>,
+    },
+    /// Sets the crossfade to the seconds typed in
+    Crossfade,
+}
+
+#[derive(Debug)]
+pub struct Prompt {
+    message: String,
+    /// Is `None` for yes/no prompts
+    input: Option<TextInput>,
+    action: PromptAction,
+}
+
+impl Prompt {
+    /// Prompt answered with yes or no
+    pub(crate) fn confirm(message: String, action: PromptAction) -> Self {
+        Self {
+            message,
+            input: None,
+            action,
+        }
+    }
+
+    /// Prompt for typing in text, starting with `value`
+    pub(crate) fn text(message: String, value: &str, action: PromptAction) -> Self {
+        Self {
+            message,
+            input: Some(TextInput::new(value)),
+            action,
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub(crate) const fn input(&self) -> Option<&TextInput> {
+        self.input.as_ref()
+    }
+
+    pub(crate) fn input_mut(&mut self) -> Option<&mut TextInput> {
+        self.input.as_mut()
+    }
+
+    pub(crate) fn into_action(self) -> (PromptAction, Option<TextInput>) {
+        (self.action, self.input)
+    }
+}
@@ -0,0 +1,806 @@
+//! Queue table which formats only the rows inside the viewport
+
+use crate::{
+    config::{Align, Column, Config, Content, Field, Width},
+    mpd::song_tag,
+    ui::human_formated_time,
+};
+use mpd::{Id, Song, Status};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    ops::{Range, RangeInclusive},
+};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, StatefulWidget, Widget},
+};
+
+const COLUMN_SPACING: u16 = 1;
+
+#[derive(Debug, Default)]
+/// Selection and scrolling of the queue along with the cells formatted so far
+pub struct QueueState {
+    selected: Option<usize>,
+    offset: usize,
+    /// Songs marked for bulk actions
+    marked: BTreeSet<Id>,
+    /// Where the visual mode was started, the range up to the cursor counts as marked
+    visual_anchor: Option<usize>,
+    /// Formatted cells of the rows by their position, valid for `cache_version` of the queue
+    cache: HashMap<usize, Vec<String>>,
+    /// Widths of the columns fitting their content, `0` for the other columns
+    fit: Vec<u16>,
+    cache_version: u32,
+    /// Album each song belongs to by its position, `None` without an album
+    group_of: Vec<Option<usize>>,
+    /// Positions of the songs of each album
+    groups: Vec<Range<usize>>,
+    /// Formatted cells of the album headers, valid for `cache_version` as well
+    group_cells: HashMap<usize, Vec<String>>,
+    /// Where the rows were rendered last, for the mouse
+    area: Rect,
+    /// Row shown on each line of `area`, `None` for the album headers
+    lines: Vec<Option<usize>>,
+    /// Where the titles of the columns were rendered last
+    header: Vec<Rect>,
+}
+
+impl QueueState {
+    pub(crate) const fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub(crate) fn select(&mut self, index: Option<usize>) {
+        self.selected = index;
+    }
+
+    /// Row at the point of the screen, counted from the first one rather than the first shown
+    pub(crate) fn row_at(&self, x: u16, y: u16) -> Option<usize> {
+        let area = self.area;
+        if !(area.left()..area.right()).contains(&x) || y < area.top() {
+            return None;
+        }
+        *self.lines.get(usize::from(y - area.y))?
+    }
+
+    /// Column whose title is at the point of the screen
+    pub(crate) fn column_at(&self, x: u16, y: u16) -> Option<usize> {
+        let point = Rect::new(x, y, 1, 1);
+        self.header.iter().position(|area| area.intersects(point))
+    }
+
+    pub(crate) fn toggle_mark(&mut self, id: Id) {
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+    }
+
+    pub(crate) fn marked(&self) -> &BTreeSet<Id> {
+        &self.marked
+    }
+
+    pub(crate) fn mark(&mut self, ids: impl IntoIterator<Item = Id>) {
+        self.marked.extend(ids);
+    }
+
+    /// Unmarks everything and leaves the visual mode
+    pub(crate) fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.visual_anchor = None;
+    }
+
+    pub(crate) const fn visual_anchor(&self) -> Option<usize> {
+        self.visual_anchor
+    }
+
+    pub(crate) fn set_visual_anchor(&mut self, anchor: Option<usize>) {
+        self.visual_anchor = anchor;
+    }
+
+    /// Range of positions selected in the visual mode
+    pub(crate) fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+        let (anchor, selected) = (self.visual_anchor?, self.selected?);
+        Some(anchor.min(selected)..=anchor.max(selected))
+    }
+
+    fn is_marked(&self, pos: usize, song: &Song) -> bool {
+        self.visual_range()
+            .is_some_and(|range| range.contains(&pos))
+            || song
+                .place
+                .is_some_and(|place| self.marked.contains(&place.id))
+    }
+}
+
+/// Widget rendering the queue
+pub struct Queue<'a> {
+    songs: &'a [Song],
+    version: u32,
+    playing: Option<usize>,
+    /// For the fields only known for the playing song
+    status: Option<&'a Status>,
+    /// Positions of the songs to show, all of them if `None`
+    rows: Option<&'a [usize]>,
+    /// Lowercased pattern to highlight in the cells
+    highlight: Option<&'a str>,
+    /// Whether the albums get a header row
+    grouped: bool,
+    config: &'a Config,
+    block: Option<Block<'a>>,
+}
+
+impl<'a> Queue<'a> {
+    pub(crate) const fn new(songs: &'a [Song], version: u32, config: &'a Config) -> Self {
+        Self {
+            songs,
+            version,
+            playing: None,
+            status: None,
+            rows: None,
+            highlight: None,
+            grouped: false,
+            config,
+            block: None,
+        }
+    }
+
+    pub(crate) const fn playing(mut self, pos: Option<usize>) -> Self {
+        self.playing = pos;
+        self
+    }
+
+    pub(crate) const fn status(mut self, status: &'a Status) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub(crate) const fn rows(mut self, rows: Option<&'a [usize]>) -> Self {
+        self.rows = rows;
+        self
+    }
+
+    pub(crate) const fn highlight(mut self, pattern: Option<&'a str>) -> Self {
+        self.highlight = pattern;
+        self
+    }
+
+    pub(crate) const fn grouped(mut self, grouped: bool) -> Self {
+        self.grouped = grouped;
+        self
+    }
+
+    pub(crate) fn block(mut self, block: Block<'a>) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    fn render_row<S: AsRef<str>>(
+        &self,
+        buf: &mut Buffer,
+        area: Rect,
+        widths: &[u16],
+        cells: &[S],
+        style: Style,
+        highlight: Option<(&str, Style)>,
+    ) {
+        buf.set_style(area, style);
+        let mut x = area.x;
+        let columns = self.config.playlist_layout();
+        for ((cell, width), column) in cells.iter().zip(widths).zip(columns) {
+            let width = (*width).min(area.right().saturating_sub(x));
+            let cell = cell.as_ref();
+            let shift = align_offset(cell, width, column.align());
+            buf.set_stringn(x + shift, area.y, cell, (width - shift).into(), style);
+
+            let range =
+                highlight.and_then(|(pattern, style)| Some((match_range(cell, pattern)?, style)));
+            if let Some(((start, len), highlight)) = range {
+                let start = (start as u16 + shift).min(width);
+                let len = (len as u16).min(width - start);
+                buf.set_style(Rect::new(x + start, area.y, len, 1), style.patch(highlight));
+            }
+            x = x.saturating_add(width + COLUMN_SPACING);
+        }
+    }
+}
+
+impl<'a> StatefulWidget for Queue<'a> {
+    type State = QueueState;
+
+    fn render(mut self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let styles = self.config.styles();
+        buf.set_style(area, styles.normal());
+        let area = match self.block.take() {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+        // header and the margin below it
+        if area.height < 3 {
+            return;
+        }
+
+        let layout = self.config.playlist_layout();
+        if state.cache_version != self.version || state.fit.len() != layout.len() {
+            state.cache.clear();
+            state.group_cells.clear();
+            state.fit = fit_widths(layout, self.songs);
+            (state.group_of, state.groups) = group_albums(self.songs);
+            state.cache_version = self.version;
+        }
+
+        // its cells can show the status, they are neither cached nor counted in the fitting widths
+        let playing = self.playing.and_then(|pos| {
+            let song = self.songs.get(pos)?;
+            let cells: Vec<String> = layout
+                .iter()
+                .map(|column| format_cell(song, column, self.status))
+                .collect();
+            Some((pos, cells))
+        });
+        let mut fit = state.fit.clone();
+        if let Some((_, cells)) = &playing {
+            for ((fit, cell), column) in fit.iter_mut().zip(cells).zip(layout) {
+                if column.width() == Width::Fit {
+                    *fit = (*fit).max(cell.chars().count() as u16);
+                }
+            }
+        }
+
+        let widths = column_widths(layout, area.width, &fit);
+        let header: Vec<&str> = layout.iter().map(Column::title).collect();
+        let header_area = Rect { height: 1, ..area };
+        self.render_row(
+            buf,
+            header_area,
+            &widths,
+            &header,
+            styles.normal().fg(Color::Cyan),
+            None,
+        );
+        let mut x = area.x;
+        state.header = widths
+            .iter()
+            .map(|width| {
+                let width = (*width).min(area.right().saturating_sub(x));
+                let title = Rect::new(x, area.y, width, 1);
+                x = x.saturating_add(width + COLUMN_SPACING);
+                title
+            })
+            .collect();
+
+        // scroll to keep the selected row visible
+        let height = (area.height - 2) as usize;
+        let len = self.rows.map_or(self.songs.len(), <[usize]>::len);
+        let pos_of = |row: usize| self.rows.map_or(row, |rows| rows[row]);
+        let header_before = |row: usize| {
+            self.grouped
+                && state.group_of.get(pos_of(row)).copied().flatten().is_some()
+                && (row == 0 || state.group_of[pos_of(row)] != state.group_of[pos_of(row - 1)])
+        };
+        state.offset = state.offset.min(len.saturating_sub(1));
+        let cursor = state.selected.map(|selected| match self.rows {
+            Some(rows) => rows.partition_point(|pos| *pos < selected),
+            None => selected,
+        });
+        if let Some(selected) = cursor {
+            if selected < state.offset {
+                state.offset = selected;
+            } else if selected >= state.offset + height {
+                state.offset = selected + 1 - height;
+            }
+            // the album headers take lines as well
+            let lines = |offset: usize| {
+                let headers = (offset..=selected)
+                    .filter(|row| header_before(*row))
+                    .count();
+                selected + 1 - offset + headers
+            };
+            while state.offset < selected && lines(state.offset) > height {
+                state.offset += 1;
+            }
+        }
+
+        state.area = Rect {
+            y: area.y + 2,
+            height: height as u16,
+            ..area
+        };
+
+        state.lines.clear();
+        let mut row = state.offset;
+        while state.lines.len() < height && row < len {
+            if header_before(row) {
+                // a header needs its song below, on a single line the song goes without it
+                if state.lines.len() + 2 <= height {
+                    state.lines.push(None);
+                } else if !state.lines.is_empty() {
+                    break;
+                }
+            }
+            state.lines.push(Some(row));
+            row += 1;
+        }
+
+        let highlight = self.highlight.map(|pattern| (pattern, styles.matched()));
+        for i in 0..state.lines.len() {
+            let row_area = Rect {
+                y: area.y + 2 + i as u16,
+                height: 1,
+                ..area
+            };
+            let Some(row) = state.lines[i] else {
+                // header of the album of the song on the next line
+                let pos = state.lines[i + 1]
+                    .map(pos_of)
+                    .expect("a song follows its header");
+                let Some(group) = state.group_of[pos] else {
+                    continue;
+                };
+                let range = state.groups[group].clone();
+                let cells = state.group_cells.entry(group).or_insert_with(|| {
+                    layout
+                        .iter()
+                        .map(|column| common_cell(&self.songs[range.clone()], column))
+                        .collect()
+                });
+                self.render_row(buf, row_area, &widths, cells, styles.group(), None);
+                continue;
+            };
+            let pos = pos_of(row);
+            let Some(song) = self.songs.get(pos) else {
+                continue;
+            };
+
+            let marked = state.is_marked(pos, song);
+            let cells = match &playing {
+                Some((playing, cells)) if *playing == pos => cells,
+                _ => state.cache.entry(pos).or_insert_with(|| {
+                    layout
+                        .iter()
+                        .map(|column| format_cell(song, column, None))
+                        .collect()
+                }),
+            };
+
+            let mut style = if self.playing == Some(pos) {
+                styles.playing()
+            } else if song.place.is_some_and(|place| place.prio > 0) {
+                styles.normal().patch(styles.queued())
+            } else {
+                styles.normal()
+            };
+            if marked {
+                style = style.patch(styles.marked());
+            }
+            if state.selected == Some(pos) {
+                style = style.patch(styles.selected());
+            }
+
+            self.render_row(buf, row_area, &widths, cells, style, highlight);
+        }
+    }
+}
+
+/// Widths of the columns fitting the songs and their title, `0` for the other columns
+pub fn fit_widths<'a>(columns: &[Column], songs: impl IntoIterator<Item = &'a Song>) -> Vec<u16> {
+    let mut fit: Vec<u16> = columns
+        .iter()
+        .map(|column| match column.width() {
+            Width::Fit => column.title().chars().count() as u16,
+            _ => 0,
+        })
+        .collect();
+    for song in songs {
+        for (fit, column) in fit.iter_mut().zip(columns) {
+            if column.width() == Width::Fit {
+                let len = format_cell(song, column, None).chars().count();
+                *fit = (*fit).max(len.try_into().unwrap_or(u16::MAX));
+            }
+        }
+    }
+    fit
+}
+
+/// Widths of the columns within `width`: the fixed and fitting ones come first,
+/// the percents are of what is left after them
+pub fn column_widths(columns: &[Column], width: u16, fit: &[u16]) -> Vec<u16> {
+    let spacing = COLUMN_SPACING * (columns.len() as u16).saturating_sub(1);
+    let mut left = width.saturating_sub(spacing);
+    let mut widths: Vec<u16> = columns
+        .iter()
+        .zip(fit)
+        .map(|(column, fit)| {
+            let width = match column.width() {
+                Width::Fixed(width) => width,
+                Width::Fit => *fit,
+                Width::Percent(_) => return 0,
+            };
+            let width = width.min(left);
+            left -= width;
+            width
+        })
+        .collect();
+    for (width, column) in widths.iter_mut().zip(columns) {
+        if let Width::Percent(percent) = column.width() {
+            *width = (u32::from(left) * u32::from(percent) / 100) as u16;
+        }
+    }
+    widths
+}
+
+/// Cells the cell is shifted by to be aligned within the width
+fn align_offset(cell: &str, width: u16, align: Align) -> u16 {
+    let free = width.saturating_sub(cell.chars().count().try_into().unwrap_or(u16::MAX));
+    match align {
+        Align::Left => 0,
+        Align::Center => free / 2,
+        Align::Right => free,
+    }
+}
+
+/// Pads or cuts the cell to the width
+pub fn align_cell(cell: &str, width: u16, align: Align) -> String {
+    let width = usize::from(width);
+    let cell: String = cell.chars().take(width).collect();
+    match align {
+        Align::Left => format!("{:<width$}", cell),
+        Align::Center => format!("{:^width$}", cell),
+        Align::Right => format!("{:>width$}", cell),
+    }
+}
+
+/// Chars of the cell matching the lowercased pattern as their offset and count
+fn match_range(cell: &str, pattern: &str) -> Option<(usize, usize)> {
+    let lowercase = cell.to_lowercase();
+    let start = lowercase.find(pattern)?;
+    let end = start + pattern.len();
+    // lowercasing can change the length of some chars, the offsets would not match then
+    if lowercase.len() != cell.len() || !cell.is_char_boundary(start) || !cell.is_char_boundary(end)
+    {
+        return None;
+    }
+
+    Some((
+        cell[..start].chars().count(),
+        cell[start..end].chars().count(),
+    ))
+}
+
+/// Albums of the songs: the one of each song and the positions of each one.
+/// An album is a run of songs sharing the album and its artist
+fn group_albums(songs: &[Song]) -> (Vec<Option<usize>>, Vec<Range<usize>>) {
+    fn key(song: &Song) -> Option<(&str, Option<&str>)> {
+        let album = song_tag(song, "Album").filter(|album| !album.is_empty())?;
+        let artist = song_tag(song, "AlbumArtist").or(song.artist.as_deref());
+        Some((album, artist))
+    }
+
+    let mut group_of = Vec::with_capacity(songs.len());
+    let mut groups: Vec<Range<usize>> = Vec::new();
+    for (pos, song) in songs.iter().enumerate() {
+        let Some(album) = key(song) else {
+            group_of.push(None);
+            continue;
+        };
+        let same = pos > 0 && group_of[pos - 1].is_some() && key(&songs[pos - 1]) == Some(album);
+        if same {
+            groups.last_mut().expect("album of the song before").end = pos + 1;
+        } else {
+            groups.push(pos..pos + 1);
+        }
+        group_of.push(Some(groups.len() - 1));
+    }
+    (group_of, groups)
+}
+
+/// Tags an album has as a whole
+const ALBUM_TAGS: [&str; 10] = [
+    "Album",
+    "AlbumSort",
+    "AlbumArtist",
+    "AlbumArtistSort",
+    "Artist",
+    "ArtistSort",
+    "Date",
+    "OriginalDate",
+    "Genre",
+    "Label",
+];
+
+/// Cell of the album's header: what the songs share in the column, empty if they differ.
+/// Columns of a single song's fields stay empty, a one song album would repeat it otherwise
+fn common_cell(songs: &[Song], column: &Column) -> String {
+    let album_wide = match column.content() {
+        Content::Fields(fields) => fields
+            .iter()
+            .all(|field| matches!(field, Field::Tag(tag) if ALBUM_TAGS.contains(tag))),
+        Content::Format(_) => songs.len() > 1,
+    };
+    if !album_wide {
+        return String::new();
+    }
+
+    let mut cells = songs.iter().map(|song| format_cell(song, column, None));
+    let first = cells.next().unwrap_or_default();
+    if cells.all(|cell| cell == first) {
+        first
+    } else {
+        String::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// What the queue is sorted by
+pub struct SortKey {
+    content: Content,
+    descending: bool,
+}
+
+impl SortKey {
+    pub(crate) const fn new(content: Content, descending: bool) -> Self {
+        Self {
+            content,
+            descending,
+        }
+    }
+}
+
+/// Positions of the songs in the order of the keys, the songs they do not tell apart
+/// keep their order
+pub fn sort_order(songs: &[Song], keys: &[SortKey]) -> Vec<usize> {
+    let values: Vec<Vec<String>> = songs
+        .iter()
+        .map(|song| {
+            keys.iter()
+                .map(|key| format_content(song, &key.content, None))
+                .collect()
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..songs.len()).collect();
+    order.sort_by(|a, b| {
+        keys.iter()
+            .zip(values[*a].iter().zip(&values[*b]))
+            .map(|(key, (a, b))| compare_values(a, b, key.descending))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    order
+}
+
+/// Natural order: runs of digits are compared as numbers and come before text,
+/// which is compared ignoring the case, so tracks like `3/12` sort before `10/12`.
+/// Empty values come last either way
+fn compare_values(a: &str, b: &str, descending: bool) -> Ordering {
+    let ordering = match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        _ => segments(a).cmp(segments(b)),
+    };
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// Part of a value compared as a whole by the natural order
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    /// Digits without the leading zeros, ordered by their count first
+    Number(usize, String),
+    Text(String),
+}
+
+fn segments(value: &str) -> impl Iterator<Item = Segment> + '_ {
+    let mut rest = value;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let digits = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        let (segment, tail) = rest.split_at(end);
+        rest = tail;
+        Some(if digits {
+            let number = segment.trim_start_matches('0');
+            Segment::Number(number.len(), number.to_string())
+        } else {
+            Segment::Text(segment.to_lowercase())
+        })
+    })
+}
+
+/// Formats a column of the song's row, `status` is of the song playing if it is the one
+pub fn format_cell(song: &Song, column: &Column, status: Option<&Status>) -> String {
+    format_content(song, column.content(), status)
+}
+
+fn format_content(song: &Song, content: &Content, status: Option<&Status>) -> String {
+    match content {
+        Content::Fields(fields) => fields
+            .iter()
+            .filter_map(|field| format_field(song, *field, status))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default(),
+        Content::Format(template) => template.text(song, status),
+    }
+}
+
+/// Value of a field of the song, `None` if it has none
+pub fn format_field(song: &Song, field: Field, status: Option<&Status>) -> Option<String> {
+    let value = match field {
+        Field::File => song.file.clone(),
+        Field::Filename => song.file.rsplit('/').next()?.to_string(),
+        Field::Extension => {
+            let filename = song.file.rsplit('/').next()?;
+            filename.rsplit_once('.')?.1.to_string()
+        }
+        Field::Duration => {
+            human_formated_time(song.duration?.as_secs().try_into().unwrap_or(u16::MAX))
+        }
+        Field::Position => (song.place?.pos + 1).to_string(),
+        Field::Priority => match song.place?.prio {
+            0 => return None,
+            prio => prio.to_string(),
+        },
+        Field::Bitrate => format!("{} kbps", status?.bitrate?),
+        Field::Format => match (song_tag(song, "Format"), status.and_then(|s| s.audio)) {
+            (Some(format), _) => format.to_string(),
+            (None, Some(audio)) => format!("{}:{}:{}", audio.rate, audio.bits, audio.chans),
+            (None, None) => return None,
+        },
+        // the mpd crate keeps these out of the tags
+        Field::Tag("Title") => song.title.clone()?,
+        Field::Tag("Artist") => song.artist.clone()?,
+        Field::Tag("Name") => song.name.clone()?,
+        Field::Tag(tag) => song_tag(song, tag)?.to_string(),
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn song(title: &str, track: &str) -> Song {
+        Song {
+            title: Some(title.to_string()),
+            tags: vec![("Track".to_string(), track.to_string())],
+            ..Song::default()
+        }
+    }
+
+    #[test]
+    fn numbers_compare_as_such() {
+        assert_eq!(compare_values("3/12", "10/12", false), Ordering::Less);
+        assert_eq!(compare_values("track 9", "track 10", false), Ordering::Less);
+        assert_eq!(compare_values("007", "7", false), Ordering::Equal);
+        assert_eq!(compare_values("3/12", "10/12", true), Ordering::Greater);
+    }
+
+    #[test]
+    fn text_ignores_the_case_and_comes_after_numbers() {
+        assert_eq!(compare_values("abc", "ABD", false), Ordering::Less);
+        assert_eq!(compare_values("Abc", "abc", false), Ordering::Equal);
+        assert_eq!(compare_values("10", "a", false), Ordering::Less);
+        assert_eq!(compare_values("a", "2", false), Ordering::Greater);
+    }
+
+    #[test]
+    fn empty_values_come_last_either_way() {
+        assert_eq!(compare_values("", "a", false), Ordering::Greater);
+        assert_eq!(compare_values("", "a", true), Ordering::Greater);
+        assert_eq!(compare_values("1", "", true), Ordering::Less);
+        assert_eq!(compare_values("", "", false), Ordering::Equal);
+    }
+
+    #[test]
+    fn comparison_is_transitive_across_numbers_and_text() {
+        // numbers against text used to be ordered by the text, numbers between them by value
+        let values = ["10", "9", "1a", "b", "", "a10", "a9", "A2"];
+        for a in values {
+            for b in values {
+                for c in values {
+                    if compare_values(a, b, false).is_le() && compare_values(b, c, false).is_le() {
+                        assert!(compare_values(a, c, false).is_le(), "{} {} {}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sorts_by_keys_in_turn() {
+        let songs = [
+            song("b", "2"),
+            song("a", "10"),
+            song("a", "2"),
+            song("c", ""),
+        ];
+        let keys = [
+            SortKey::new(Content::Fields(vec![Field::Tag("Title")]), false),
+            SortKey::new(Content::Fields(vec![Field::Tag("Track")]), true),
+        ];
+        assert_eq!(sort_order(&songs, &keys), [1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn grouped_rows_fit_any_height() {
+        let songs: Vec<Song> = (0..20)
+            .map(|i| Song {
+                file: format!("{}.flac", i),
+                tags: vec![("Album".to_string(), format!("Album {}", i / 4))],
+                ..Song::default()
+            })
+            .collect();
+        let config = Config::default();
+        for height in 3..16 {
+            for selected in [0, 3, 4, 10, 19] {
+                let area = Rect::new(0, 0, 80, height);
+                let mut buf = Buffer::empty(area);
+                let mut state = QueueState::default();
+                state.select(Some(selected));
+                Queue::new(&songs, 1, &config)
+                    .grouped(true)
+                    .render(area, &mut buf, &mut state);
+
+                assert!(state.lines.len() <= height as usize - 2);
+                assert!(state.lines.contains(&Some(selected)));
+                // every header is followed by a song of its album
+                for (i, line) in state.lines.iter().enumerate() {
+                    if line.is_none() {
+                        assert!(matches!(state.lines.get(i + 1), Some(Some(_))));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored`, fails if a frame takes a millisecond
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_render_100k_queue() {
+        let songs: Vec<Song> = (0..100_000)
+            .map(|i| Song {
+                file: format!("music/artist {}/album {}/{}.flac", i % 300, i % 7000, i),
+                title: Some(format!("Title {}", i)),
+                artist: Some(format!("Artist {}", i % 300)),
+                duration: Some(Duration::from_secs(200 + i % 100)),
+                tags: vec![
+                    ("Album".to_string(), format!("Album {}", i % 7000)),
+                    ("Track".to_string(), (i % 12 + 1).to_string()),
+                ],
+                ..Song::default()
+            })
+            .collect();
+        let config = Config::default();
+        let area = Rect::new(0, 0, 200, 60);
+        let mut buf = Buffer::empty(area);
+        let mut state = QueueState::default();
+
+        let frames = 1000;
+        let start = Instant::now();
+        for frame in 0..frames {
+            // jump far enough for every frame to format rows which are not cached yet
+            state.select(Some(frame * 97 % songs.len()));
+            Queue::new(&songs, 1, &config)
+                .playing(Some(42))
+                .render(area, &mut buf, &mut state);
+        }
+        let per_frame = start.elapsed() / frames as u32;
+        assert!(
+            per_frame < Duration::from_millis(1),
+            "{:?} per frame",
+            per_frame
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Search across MPD's database
+
+use crate::{
+    mpd::{AddMode, Mpd},
+    ui::{queue::QueueState, text_input::TextInput},
+};
+use mpd::{Song, Term};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Tag the query is matched against
+pub enum SearchScope {
+    #[default]
+    Any,
+    Artist,
+    Album,
+    Title,
+    File,
+    Genre,
+}
+
+impl SearchScope {
+    pub(crate) const fn name(self) -> &'static str {
+        match self {
+            Self::Any => "Any",
+            Self::Artist => "Artist",
+            Self::Album => "Album",
+            Self::Title => "Title",
+            Self::File => "File",
+            Self::Genre => "Genre",
+        }
+    }
+
+    /// Scope named `name`, ignoring the case
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::all().find(|scope| scope.name().eq_ignore_ascii_case(name))
+    }
+
+    pub(crate) fn all() -> impl Iterator<Item = Self> {
+        std::iter::successors(Some(Self::Any), |scope| {
+            Some(scope.next()).filter(|next| *next != Self::Any)
+        })
+    }
+
+    fn term(self) -> Term<'static> {
+        match self {
+            Self::Any => Term::Any,
+            Self::File => Term::File,
+            scope => Term::Tag(scope.name().into()),
+        }
+    }
+
+    const fn next(self) -> Self {
+        match self {
+            Self::Any => Self::Artist,
+            Self::Artist => Self::Album,
+            Self::Album => Self::Title,
+            Self::Title => Self::File,
+            Self::File => Self::Genre,
+            Self::Genre => Self::Any,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Query being typed and the songs found by the last one
+pub struct Search {
+    input: TextInput,
+    /// Whether keys go to the input
+    editing: bool,
+    scope: SearchScope,
+    /// `find` matching whole values or `search` matching substrings, ignoring the case
+    exact: bool,
+    results: Vec<Song>,
+    /// Bumped on every search, so the rows formatted for the previous results are dropped
+    version: u32,
+    state: QueueState,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            input: TextInput::default(),
+            editing: true,
+            scope: SearchScope::default(),
+            exact: false,
+            results: Vec::new(),
+            version: 0,
+            state: QueueState::default(),
+        }
+    }
+}
+
+impl Search {
+    /// Runs the query typed in and stops editing it
+    pub(crate) fn run(&mut self, mpd: &mut Mpd) {
+        self.editing = false;
+        let query = self.input.value();
+        if query.is_empty() {
+            return;
+        }
+
+        let results = mpd.search_songs(self.scope.term(), query, self.exact);
+        self.results = results.unwrap_or_default();
+        self.version = self.version.wrapping_add(1);
+        self.state.select((!self.results.is_empty()).then_some(0));
+    }
+
+    /// Replaces the query and the tag it is matched against, without running it
+    pub(crate) fn set_query(&mut self, scope: SearchScope, query: &str) {
+        self.scope = scope;
+        self.input = TextInput::new(query);
+    }
+
+    pub(crate) fn start_editing(&mut self) {
+        self.editing = true;
+    }
+
+    pub(crate) fn stop_editing(&mut self) {
+        self.editing = false;
+    }
+
+    /// Matches the query against the next tag, searching again
+    pub(crate) fn next_scope(&mut self, mpd: &mut Mpd) {
+        self.scope = self.scope.next();
+        self.run(mpd);
+    }
+
+    /// Switches between exact and substring matching, searching again
+    pub(crate) fn toggle_exact(&mut self, mpd: &mut Mpd) {
+        self.exact = !self.exact;
+        self.run(mpd);
+    }
+
+    pub(crate) fn next(&mut self) {
+        let len = self.results.len();
+        if len == 0 {
+            return;
+        }
+        let i = self.state.selected().map_or(0, |i| (i + 1) % len);
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let len = self.results.len();
+        if len == 0 {
+            return;
+        }
+        let i = self
+            .state
+            .selected()
+            .map_or(0, |i| i.checked_sub(1).unwrap_or(len - 1));
+        self.state.select(Some(i));
+    }
+
+    /// Adds the selected result to the queue
+    pub(crate) fn add(&self, mpd: &mut Mpd, mode: AddMode) {
+        let Some(pos) = self
+            .state
+            .selected()
+            .filter(|pos| *pos < self.results.len())
+        else {
+            return;
+        };
+        mpd.add_songs(&self.results[pos..=pos], mode);
+    }
+
+    /// Adds every result to the queue
+    pub(crate) fn add_all(&self, mpd: &mut Mpd) {
+        mpd.add_songs(&self.results, AddMode::Append);
+    }
+
+    pub(crate) const fn input(&self) -> &TextInput {
+        &self.input
+    }
+
+    pub(crate) fn input_mut(&mut self) -> &mut TextInput {
+        &mut self.input
+    }
+
+    pub(crate) const fn editing(&self) -> bool {
+        self.editing
+    }
+
+    pub(crate) const fn scope(&self) -> SearchScope {
+        self.scope
+    }
+
+    pub(crate) const fn exact(&self) -> bool {
+        self.exact
+    }
+
+    pub(crate) const fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Results along with the selection in them, for rendering
+    pub(crate) fn results_mut(&mut self) -> (&[Song], &mut QueueState) {
+        (&self.results, &mut self.state)
+    }
+}
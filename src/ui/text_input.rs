@@ -0,0 +1,191 @@
+//! Single line text input
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+#[derive(Debug, Default, Clone)]
+/// Text being typed along with the cursor, which is counted in chars
+pub struct TextInput {
+    value: String,
+    cursor: usize,
+}
+
+impl TextInput {
+    pub(crate) fn new(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+        }
+    }
+
+    pub(crate) fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Edits the text or moves the cursor, returns whether the key was used
+    pub(crate) fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let len = self.value.chars().count();
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return self.handle_control(key.code);
+        }
+
+        match key.code {
+            KeyCode::Char(c) => {
+                let i = self.byte_index(self.cursor);
+                self.value.insert(i, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let i = self.byte_index(self.cursor);
+                self.value.remove(i);
+            }
+            KeyCode::Delete if self.cursor < len => {
+                let i = self.byte_index(self.cursor);
+                self.value.remove(i);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Backspace | KeyCode::Delete => (),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Readline-like shortcuts
+    fn handle_control(&mut self, code: KeyCode) -> bool {
+        match code {
+            // start and end of the line
+            KeyCode::Char('a') => self.cursor = 0,
+            KeyCode::Char('e') => self.cursor = self.value.chars().count(),
+            // delete to the start of the line
+            KeyCode::Char('u') => {
+                let i = self.byte_index(self.cursor);
+                self.value.replace_range(..i, "");
+                self.cursor = 0;
+            }
+            // delete the word before the cursor
+            KeyCode::Char('w') => {
+                let end = self.byte_index(self.cursor);
+                let before = self.value[..end].trim_end();
+                let start = before
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| c.is_whitespace())
+                    .map_or(0, |(i, c)| i + c.len_utf8());
+                self.cursor -= self.value[start..end].chars().count();
+                self.value.replace_range(start..end, "");
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.value.len(), |(i, _)| i)
+    }
+
+    /// How many chars are scrolled out on the left for the cursor to fit in `width`
+    fn scroll(&self, width: u16) -> usize {
+        self.cursor
+            .saturating_sub(usize::from(width).saturating_sub(1))
+    }
+
+    /// Where the terminal's cursor goes when the input is rendered in `area`
+    pub(crate) fn cursor_position(&self, area: Rect) -> (u16, u16) {
+        let x = (self.cursor - self.scroll(area.width)) as u16;
+        (area.x + x, area.y)
+    }
+}
+
+impl Widget for &TextInput {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.height == 0 {
+            return;
+        }
+        let visible: String = self.value.chars().skip(self.scroll(area.width)).collect();
+        buf.set_stringn(area.x, area.y, visible, area.width.into(), Style::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut TextInput, code: KeyCode) {
+        input.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn control(input: &mut TextInput, c: char) {
+        input.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn edits_at_the_cursor_counted_in_chars() {
+        let mut input = TextInput::new("héllo");
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Char('ß'));
+        assert_eq!(input.value(), "hélßlo");
+        press(&mut input, KeyCode::Home);
+        press(&mut input, KeyCode::Right);
+        press(&mut input, KeyCode::Delete);
+        assert_eq!(input.value(), "hlßlo");
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!(input.value(), "lßlo");
+        assert_eq!(input.cursor, 0);
+    }
+
+    #[test]
+    fn cursor_stays_within_the_text() {
+        let mut input = TextInput::new("ab");
+        press(&mut input, KeyCode::Right);
+        assert_eq!(input.cursor, 2);
+        press(&mut input, KeyCode::Home);
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!((input.value(), input.cursor), ("ab", 0));
+    }
+
+    #[test]
+    fn deletes_the_word_before_the_cursor() {
+        let mut input = TextInput::new("add  some songs ");
+        control(&mut input, 'w');
+        assert_eq!(input.value(), "add  some ");
+        control(&mut input, 'w');
+        assert_eq!(input.value(), "add  ");
+        control(&mut input, 'w');
+        assert_eq!((input.value(), input.cursor), ("", 0));
+    }
+
+    #[test]
+    fn deletes_words_split_by_wide_whitespace() {
+        let mut input = TextInput::new("曲\u{3000}名前");
+        control(&mut input, 'w');
+        assert_eq!((input.value(), input.cursor), ("曲\u{3000}", 2));
+        control(&mut input, 'w');
+        assert_eq!(input.value(), "");
+    }
+
+    #[test]
+    fn deletes_to_the_start_of_the_line() {
+        let mut input = TextInput::new("ünï cödé");
+        for _ in 0..4 {
+            press(&mut input, KeyCode::Left);
+        }
+        control(&mut input, 'u');
+        assert_eq!((input.value(), input.cursor), ("cödé", 0));
+    }
+
+    #[test]
+    fn scrolls_to_keep_the_cursor_visible() {
+        let input = TextInput::new("0123456789");
+        assert_eq!(input.scroll(4), 7);
+        assert_eq!(input.cursor_position(Rect::new(2, 1, 4, 1)), (5, 1));
+    }
+}